@@ -1,6 +1,10 @@
 use lazy_static::lazy_static;
 use std::net::SocketAddr;
 use std::env;
+use std::path::PathBuf;
+use crate::shortcuts::blobs::BlobStoreKind;
+use crate::utils::kebab::{CharacterClass, Skewer, SkewerOptions};
+use crate::utils::policy::{NamePolicy, WordFilter};
 
 
 lazy_static! {
@@ -14,4 +18,126 @@ lazy_static! {
 
     pub(crate) static ref CREATE_TOKEN: String = env::var("CREATE_TOKEN")
         .expect("CREATE_TOKEN to be set");
+
+    pub(crate) static ref ADMIN_TOKEN: Option<String> = env::var("ADMIN_TOKEN")
+        .ok();
+
+    pub(crate) static ref TOKEN_ID_SECRET: Option<String> = env::var("TOKEN_ID_SECRET")
+        .ok()
+        .filter(|secret| !secret.is_empty());
+
+    pub(crate) static ref AUDIT_MAX_LENGTH: usize = env::var("AUDIT_MAX_LENGTH")
+        .unwrap_or_else(|_| "100000".to_string())
+        .parse()
+        .expect("AUDIT_MAX_LENGTH to be a valid number of entries");
+
+    pub(crate) static ref STATS_CACHE_SECONDS: usize = env::var("STATS_CACHE_SECONDS")
+        .unwrap_or_else(|_| "10".to_string())
+        .parse()
+        .expect("STATS_CACHE_SECONDS to be a valid number of seconds");
+
+    pub(crate) static ref LIVE_MAX_SUBSCRIBERS: usize = env::var("LIVE_MAX_SUBSCRIBERS")
+        .unwrap_or_else(|_| "1000".to_string())
+        .parse()
+        .expect("LIVE_MAX_SUBSCRIBERS to be a valid number of subscribers");
+
+    pub(crate) static ref PERIODS_TIMEZONE: chrono_tz::Tz = env::var("PERIODS_TIMEZONE")
+        .unwrap_or_else(|_| "UTC".to_string())
+        .parse()
+        .expect("PERIODS_TIMEZONE to be a valid IANA timezone name");
+
+    pub(crate) static ref PERIODS_RETENTION_SECONDS: i64 = env::var("PERIODS_RETENTION_SECONDS")
+        .unwrap_or_else(|_| "604800".to_string())
+        .parse()
+        .expect("PERIODS_RETENTION_SECONDS to be a valid number of seconds");
+
+    pub(crate) static ref NAMES_UNICODE: bool = env::var("NAMES_UNICODE")
+        .unwrap_or_else(|_| "false".to_string())
+        .parse()
+        .expect("NAMES_UNICODE to be either true or false");
+
+    pub(crate) static ref PLAYER_NAMES: SkewerOptions = SkewerOptions {
+        normalize: *NAMES_UNICODE,
+        transliterate: env::var("NAMES_TRANSLITERATE")
+            .unwrap_or_else(|_| "false".to_string())
+            .parse()
+            .expect("NAMES_TRANSLITERATE to be either true or false"),
+        allowed: env::var("NAMES_ALLOWED_CHARACTERS")
+            .unwrap_or_else(|_| match *NAMES_UNICODE {
+                true => "Letters,Digits".to_string(),
+                false => "AsciiLetters,AsciiDigits".to_string(),
+            })
+            .split(',')
+            .map(|class| CharacterClass::try_from(class.trim()))
+            .collect::<Result<Vec<CharacterClass>, ()>>()
+            .expect("NAMES_ALLOWED_CHARACTERS to be a comma-separated list of character classes"),
+    };
+
+    pub(crate) static ref PLAYER_NAME_POLICY: NamePolicy = NamePolicy {
+        min_length: env::var("NAMES_MIN_LENGTH")
+            .unwrap_or_else(|_| "1".to_string())
+            .parse()
+            .expect("NAMES_MIN_LENGTH to be a valid number of characters"),
+        max_length: env::var("NAMES_MAX_LENGTH")
+            .unwrap_or_else(|_| "32".to_string())
+            .parse()
+            .expect("NAMES_MAX_LENGTH to be a valid number of characters"),
+        reserved: env::var("NAMES_RESERVED")
+            .unwrap_or_else(|_| "admin,moderator".to_string())
+            .split(',')
+            .map(|name| name.to_kebab_lowercase_with(&PLAYER_NAMES))
+            .collect(),
+        filter: env::var("NAMES_PROFANITY_FILE")
+            .ok()
+            .map(|path| WordFilter::from_file(path).expect("NAMES_PROFANITY_FILE to be a readable file")),
+    };
+
+    pub(crate) static ref WEBHOOKS_MAX_ATTEMPTS: u32 = env::var("WEBHOOKS_MAX_ATTEMPTS")
+        .unwrap_or_else(|_| "8".to_string())
+        .parse()
+        .expect("WEBHOOKS_MAX_ATTEMPTS to be a valid number of attempts");
+
+    pub(crate) static ref WEBHOOKS_TIMEOUT_SECONDS: u64 = env::var("WEBHOOKS_TIMEOUT_SECONDS")
+        .unwrap_or_else(|_| "10".to_string())
+        .parse()
+        .expect("WEBHOOKS_TIMEOUT_SECONDS to be a valid number of seconds");
+
+    pub(crate) static ref IDEMPOTENCY_TTL_SECONDS: usize = env::var("IDEMPOTENCY_TTL_SECONDS")
+        .unwrap_or_else(|_| "86400".to_string())
+        .parse()
+        .ok()
+        .filter(|ttl| *ttl > 0)
+        .expect("IDEMPOTENCY_TTL_SECONDS to be a positive number of seconds");
+
+    pub(crate) static ref SYNC_MAX_CLOCK_SKEW_SECONDS: i64 = env::var("SYNC_MAX_CLOCK_SKEW_SECONDS")
+        .unwrap_or_else(|_| "300".to_string())
+        .parse()
+        .expect("SYNC_MAX_CLOCK_SKEW_SECONDS to be a valid number of seconds");
+
+    pub(crate) static ref SYNC_MAX_BACKDATE_SECONDS: i64 = env::var("SYNC_MAX_BACKDATE_SECONDS")
+        .unwrap_or_else(|_| "604800".to_string())
+        .parse()
+        .expect("SYNC_MAX_BACKDATE_SECONDS to be a valid number of seconds");
+
+    pub(crate) static ref SYNC_CLOSE_GRACE_SECONDS: i64 = env::var("SYNC_CLOSE_GRACE_SECONDS")
+        .unwrap_or_else(|_| "0".to_string())
+        .parse()
+        .expect("SYNC_CLOSE_GRACE_SECONDS to be a valid number of seconds");
+
+    pub(crate) static ref SCORES_METADATA_MAX_BYTES: usize = env::var("SCORES_METADATA_MAX_BYTES")
+        .unwrap_or_else(|_| "4096".to_string())
+        .parse()
+        .expect("SCORES_METADATA_MAX_BYTES to be a valid number of bytes");
+
+    pub(crate) static ref REPLAYS_MAX_BYTES: usize = env::var("REPLAYS_MAX_BYTES")
+        .unwrap_or_else(|_| "1048576".to_string())
+        .parse()
+        .expect("REPLAYS_MAX_BYTES to be a valid number of bytes");
+
+    pub(crate) static ref REPLAYS_STORE: BlobStoreKind = BlobStoreKind::try_from(env::var("REPLAYS_STORE").unwrap_or_else(|_| "Filesystem".to_string()).as_str())
+        .expect("REPLAYS_STORE to be either Filesystem or Redis");
+
+    pub(crate) static ref REPLAYS_DIR: PathBuf = env::var("REPLAYS_DIR")
+        .unwrap_or_else(|_| "replays".to_string())
+        .into();
 }
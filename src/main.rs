@@ -3,9 +3,11 @@ pub(crate) mod outcome;
 pub mod utils;
 mod routes;
 mod shortcuts;
+mod tasks;
 
 
-use axum::routing::{get, post, put};
+use std::net::SocketAddr;
+use axum::routing::{delete, get, post, put};
 
 
 #[tokio::main]
@@ -13,11 +15,33 @@ async fn main() {
     pretty_env_logger::init();
     log::debug!("Logging initialized!");
 
+    log::debug!("Checking configuration...");
+
+    lazy_static::initialize(&config::IDEMPOTENCY_TTL_SECONDS);
+    lazy_static::initialize(&config::PLAYER_NAME_POLICY);
+
     log::debug!("Opening Redis client...");
 
     let rclient = redis::Client::open(&**config::REDIS_CONN)
         .expect("to be able to connect to Redis");
 
+    log::debug!("Opening blob store...");
+
+    let blobs = shortcuts::blobs::blob_store_from_config(&rclient);
+
+    log::debug!("Determining the secret of token ids...");
+
+    lazy_static::initialize(&shortcuts::token::TOKEN_ID_SECRET);
+
+    log::debug!("Opening live subscriptions hub...");
+
+    let live = shortcuts::live::LiveHub::new(rclient.clone());
+
+    log::debug!("Starting background tasks...");
+
+    tokio::spawn(tasks::seasons::run(rclient.clone()));
+    tokio::spawn(tasks::webhooks::run(rclient.clone()));
+
     log::debug!("Configuring Axum router...");
 
     let webapp = axum::Router::new()
@@ -25,8 +49,36 @@ async fn main() {
         .route("/", post(routes::home::route_home_post))
         .route("/board/", get(routes::board::route_board_get))
         .route("/board/", post(routes::board::route_board_post))
+        .route("/board/around/", get(routes::around::route_board_around_get))
+        .route("/board/export/", get(routes::export::route_board_export_get))
+        .route("/board/stats/", get(routes::stats::route_board_stats_get))
+        .route("/board/live/", get(routes::live::route_board_live_get))
+        .route("/board/season/", get(routes::season::route_board_season_get))
+        .route("/board/season/", post(routes::season::route_board_season_post))
+        .route("/board/state/", get(routes::state::route_board_state_get))
+        .route("/board/state/", put(routes::state::route_board_state_put))
+        .route("/board/info/", get(routes::info::route_board_info_get))
+        .route("/board/info/", put(routes::info::route_board_info_put))
+        .route("/board/webhooks/", get(routes::webhooks::route_board_webhooks_get))
+        .route("/board/webhooks/", post(routes::webhooks::route_board_webhooks_post))
+        .route("/board/webhooks/", delete(routes::webhooks::route_board_webhooks_delete))
+        .route("/board/webhooks/deliveries/", get(routes::webhooks::route_board_webhooks_deliveries_get))
         .route("/score/", get(routes::score::route_score_get))
         .route("/score/", put(routes::score::route_score_put))
+        .route("/score/rank/", get(routes::rank::route_score_rank_get))
+        .route("/score/batch/", post(routes::batch::route_score_batch_post))
+        .route("/score/sync/", post(routes::sync::route_score_sync_post))
+        .route("/score/replay/", get(routes::replay::route_score_replay_get))
+        .route("/score/replay/", put(routes::replay::route_score_replay_put).layer(axum::extract::DefaultBodyLimit::max(*config::REPLAYS_MAX_BYTES)))
+        .route("/score/moderation/", put(routes::moderation::route_score_moderation_put))
+        .route("/score/moderation/", delete(routes::moderation::route_score_moderation_delete))
+        .route("/player/", post(routes::player::route_player_post))
+        .route("/ban/", get(routes::ban::route_ban_get))
+        .route("/ban/", put(routes::ban::route_ban_put))
+        .route("/ban/", delete(routes::ban::route_ban_delete))
+        .route("/audit/", get(routes::audit::route_audit_get))
+        .layer(axum::Extension(live))
+        .layer(axum::Extension(blobs))
         .layer(axum::Extension(rclient))
         .layer(tower_http::cors::CorsLayer::new()
             .allow_origin(
@@ -35,14 +87,18 @@ async fn main() {
             .allow_headers([
                 axum::http::header::AUTHORIZATION,
                 axum::http::header::CONTENT_TYPE,
+                axum::http::HeaderName::from_static(shortcuts::idempotency::IDEMPOTENCY_KEY_HEADER),
             ])
             .allow_methods(
                 tower_http::cors::Any
             )
+            .expose_headers([
+                axum::http::HeaderName::from_static(routes::board::NEXT_CURSOR_HEADER),
+            ])
         );
 
     log::info!("Starting Axum server...");
 
-    axum::Server::bind(&config::AXUM_HOST).serve(webapp.into_make_service()).await
+    axum::Server::bind(&config::AXUM_HOST).serve(webapp.into_make_service_with_connect_info::<SocketAddr>()).await
         .expect("to be able to run the Axum server");
 }
@@ -0,0 +1,176 @@
+//! Module defining routes for `/board/around/`.
+
+use axum::http::StatusCode;
+use axum::extract::{Extension, Query};
+use redis::AsyncCommands;
+use serde::Serialize;
+use serde::Deserialize;
+use crate::outcome;
+use crate::shortcuts::ban::{get_ban_kind, shadow_scores_key, BanKind};
+use crate::shortcuts::board::{get_score_kind, get_score_schema};
+use crate::shortcuts::exact::{exact_scores_key, get_exact_score, get_exact_scores, EXACT_LUA};
+use crate::shortcuts::names::get_display_names;
+use crate::shortcuts::redis::RedisConnectOr504;
+use crate::utils::kebab::Skewer;
+use crate::utils::kind::ScoreNumber;
+use crate::utils::sorting::SortingOrder;
+use crate::config;
+
+
+/// Expected query params for [`GET /board/around/`](route_board_around_get).
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(crate) struct RouteBoardAroundQuery {
+    /// The name of the board to access.
+    pub(crate) board: String,
+    /// The name of the player to center the window on.
+    pub(crate) player: String,
+    /// How many scores to return above and below the player.
+    pub(crate) radius: usize,
+}
+
+
+/// A score set by a player, along with its position on the board.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(crate) struct RankedScoreObject {
+    /// The position of the score relative to the other scores on the board, zero-based.
+    pub(crate) rank: usize,
+    /// The normalized name of the player who set the score.
+    pub(crate) name: String,
+    /// The name of the player who set the score, as it was originally submitted.
+    pub(crate) display_name: String,
+    /// The score that the player set.
+    pub(crate) score: ScoreNumber,
+    /// The values of the columns of the score, if the board has multiple.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) columns: Option<Vec<f64>>,
+}
+
+
+lazy_static::lazy_static! {
+    /// Script finding the rank of a player and the scores surrounding it in a single atomic step.
+    ///
+    /// - `KEYS[1]`: the scores key of the board
+    /// - `KEYS[2]`: the key of the exact values of the scores of the board
+    /// - `KEYS[3]`: the key of the hidden scores of the board
+    /// - `KEYS[4]`: the key of the exact values of the hidden scores of the board
+    /// - `ARGV[1]`: the [`SortingOrder`] of the board, as stored in Redis
+    /// - `ARGV[2]`: the [`ScoreKind`](crate::utils::kind::ScoreKind) of the board, as stored in Redis
+    /// - `ARGV[3]`: the player to center the window on
+    /// - `ARGV[4]`: the radius of the window
+    /// - `ARGV[5]`: `1` if the player is shadow-banned, and their hidden score should be shown among the visible ones, or `0` otherwise
+    ///
+    /// Returns `nil` if the player has no score, or the rank of the first returned score followed by the scores.
+    static ref AROUND_SCRIPT: redis::Script = redis::Script::new(&[EXACT_LUA, r#"
+        local rev = ARGV[1] == "Descending"
+        local exact = ARGV[2] == "Integer" and KEYS[2] or nil
+        local radius = tonumber(ARGV[4])
+        local score = redis.call("ZSCORE", KEYS[1], ARGV[3])
+        if score then
+            local position = rank(KEYS[1], exact, rev, score, exact and redis.call("HGET", exact, ARGV[3]), ARGV[3])
+            local start = math.max(position - radius, 0)
+            return {start, range(KEYS[1], exact, rev, start, position + radius)}
+        end
+
+        score = ARGV[5] == "1" and redis.call("ZSCORE", KEYS[3], ARGV[3])
+        if not score then
+            return false
+        end
+        local position = rank(KEYS[1], exact, rev, score, exact and redis.call("HGET", KEYS[4], ARGV[3]), ARGV[3])
+        local start = math.max(position - radius, 0)
+        -- The visible scores ranked after the hidden one are shifted down by one.
+        local entries = {}
+        if position + radius - 1 >= start then
+            entries = range(KEYS[1], exact, rev, start, position + radius - 1)
+        end
+        local index = (position - start) * 2 + 1
+        table.insert(entries, index, ARGV[3])
+        table.insert(entries, index + 1, score)
+        return {start, entries}
+    "#].concat());
+}
+
+
+/// Handler for `GET /board/around/`.
+pub(crate) async fn route_board_around_get(
+    // Request query
+    Query(RouteBoardAroundQuery {board, player, radius}): Query<RouteBoardAroundQuery>,
+    // Redis client
+    Extension(rclient): Extension<redis::Client>,
+) -> outcome::RequestResult {
+
+    let board = board.to_kebab_lowercase();
+    let player = player.to_kebab_lowercase_with(&config::PLAYER_NAMES);
+
+    log::trace!("Ensuring the radius is within limits...");
+    if radius > 250 {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            outcome::req_error!("Cannot request a radius larger than 250 scores")
+        ))
+    }
+
+    log::trace!("Determining the Redis key names...");
+    let order_key = format!("board:{board}:order");
+    let scores_key = format!("board:{board}:scores");
+    let shadow_key = shadow_scores_key(&board);
+
+    let mut rconn = rclient.get_connection_or_504().await?;
+
+    log::trace!("Determining sorting order...");
+    let order = rconn.get::<&str, String>(&order_key).await
+        .map_err(outcome::redis_cmd_failed)?;
+    let order = SortingOrder::try_from(order.as_str())
+        .map_err(|_| outcome::redis_unexpected_behaviour())?;
+    log::trace!("Sorting order is: {order:?}");
+
+    let kind = get_score_kind(&mut rconn, &board).await?;
+    let schema = get_score_schema(&mut rconn, &board).await?;
+
+    let shadow = matches!(get_ban_kind(&mut rconn, &board, &player).await?, Some(BanKind::ShadowBan));
+
+    log::trace!("Retrieving scores around {player} from {board}...");
+    let (start, scores) = AROUND_SCRIPT
+        .key(&scores_key)
+        .key(exact_scores_key(&scores_key))
+        .key(&shadow_key)
+        .key(exact_scores_key(&shadow_key))
+        .arg(Into::<&str>::into(order))
+        .arg(Into::<&str>::into(kind))
+        .arg(&player)
+        .arg(radius)
+        .arg(if shadow { "1" } else { "0" })
+        .invoke_async::<redis::aio::Connection, Option<(usize, Vec<(String, f64)>)>>(&mut rconn).await
+        .map_err(outcome::redis_cmd_failed)?
+        .ok_or_else(|| (StatusCode::NOT_FOUND, outcome::req_error!("Player has no score on this board")))?;
+
+    let mut scores = get_exact_scores(&mut rconn, &scores_key, kind, scores).await
+        .map_err(outcome::redis_cmd_failed)?;
+
+    if shadow {
+        log::trace!("Retrieving the exact value of the hidden score...");
+        for (name, score) in scores.iter_mut().filter(|(name, _)| *name == player) {
+            *score = get_exact_score(&mut rconn, &shadow_key, kind, name, score.as_f64()).await
+                .map_err(outcome::redis_cmd_failed)?;
+        }
+    }
+
+    log::trace!("Retrieving display names...");
+    let players: Vec<&str> = scores.iter().map(|(name, _)| name.as_str()).collect();
+    let names = get_display_names(&mut rconn, &board, None, &players).await
+        .map_err(outcome::redis_cmd_failed)?;
+
+    let result: Vec<RankedScoreObject> = scores
+        .into_iter()
+        .zip(names)
+        .enumerate()
+        .map(|(index, ((name, score), display_name))| RankedScoreObject {
+            rank: start + index,
+            name,
+            display_name,
+            score,
+            columns: schema.as_ref().map(|schema| schema.decode(score.as_f64(), order)),
+        })
+        .collect();
+
+    Ok((StatusCode::OK, outcome::req_success!(result)))
+}
@@ -0,0 +1,90 @@
+//! Module defining routes for `/audit/`.
+
+use std::collections::HashMap;
+use axum::http::{HeaderMap, StatusCode};
+use axum::extract::{Extension, Query};
+use serde::Serialize;
+use serde::Deserialize;
+use crate::outcome;
+use crate::shortcuts::audit::audit_key;
+use crate::shortcuts::redis::RedisConnectOr504;
+use crate::shortcuts::token::{Authorize, CheckBoardToken};
+use crate::utils::kebab::Skewer;
+
+
+/// Expected query params for [`GET /audit/`](route_audit_get).
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(crate) struct RouteAuditQuery {
+    /// The board to retrieve the audit log of, or [`None`] to retrieve the global audit log.
+    pub(crate) board: Option<String>,
+    /// The UNIX timestamp from which entries should be returned, inclusive.
+    pub(crate) since: Option<i64>,
+    /// The UNIX timestamp until which entries should be returned, inclusive.
+    pub(crate) until: Option<i64>,
+    /// The id of the entry after which entries should be returned, overriding [`since`](Self::since).
+    pub(crate) after: Option<String>,
+    /// How many entries to return.
+    pub(crate) size: usize,
+}
+
+
+/// An entry of an audit log, as a serializable struct.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(crate) struct AuditEntryObject {
+    /// The id of the entry in the stream.
+    pub(crate) id: String,
+    /// The UNIX timestamp in milliseconds at which the entry was recorded.
+    pub(crate) timestamp: i64,
+    /// The details of the recorded action.
+    #[serde(flatten)]
+    pub(crate) fields: HashMap<String, String>,
+}
+
+
+/// Handler for `GET /audit/`.
+pub(crate) async fn route_audit_get(
+    // Redis client
+    Extension(rclient): Extension<redis::Client>,
+    // Request headers
+    headers: HeaderMap,
+    // Request query
+    Query(RouteAuditQuery {board, since, until, after, size}): Query<RouteAuditQuery>,
+) -> outcome::RequestResult {
+    let board = board.map(|board| board.to_kebab_lowercase());
+
+    log::trace!("Ensuring the size is within limits...");
+    if size > 1000 {
+        return Err((StatusCode::BAD_REQUEST, outcome::req_error!("Cannot request more than 1000 entries")))
+    }
+
+    let token = headers.get_authorization_or_401("Bearer")?;
+    let mut rconn = rclient.get_connection_or_504().await?;
+
+    rconn.check_manager_token_or_403(board.as_deref(), token).await?;
+
+    let start = match (after, since) {
+        (Some(after), _) => format!("({after}"),
+        (None, Some(since)) => format!("{}", since * 1000),
+        (None, None) => "-".to_string(),
+    };
+    let end = match until {
+        Some(until) => format!("{}", until * 1000 + 999),
+        None => "+".to_string(),
+    };
+
+    log::trace!("Retrieving audit log entries from {start:?} to {end:?}...");
+    let entries = redis::cmd("XRANGE").arg(audit_key(board.as_deref())).arg(&start).arg(&end).arg("COUNT").arg(size)
+        .query_async::<redis::aio::Connection, Vec<(String, HashMap<String, String>)>>(&mut rconn).await
+        .map_err(outcome::redis_cmd_failed)?;
+
+    let result = entries.into_iter()
+        .map(|(id, fields)| {
+            let timestamp = id.split('-').next()
+                .and_then(|timestamp| timestamp.parse().ok())
+                .ok_or_else(outcome::redis_unexpected_behaviour)?;
+            Ok(AuditEntryObject {id, timestamp, fields})
+        })
+        .collect::<Result<Vec<AuditEntryObject>, outcome::RequestTuple>>()?;
+
+    Ok((StatusCode::OK, outcome::req_success!(result)))
+}
@@ -0,0 +1,280 @@
+//! Module defining routes for `/ban/`.
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use axum::http::{HeaderMap, StatusCode};
+use axum::extract::{ConnectInfo, Extension, Json, Query};
+use redis::AsyncCommands;
+use serde::Serialize;
+use serde::Deserialize;
+use crate::outcome;
+use crate::shortcuts::audit::{audit_to, Origin};
+use crate::shortcuts::ban::{bans_key, get_all_boards, get_ban_kind, hide_score, restore_score, BanKind, BanObject};
+use crate::shortcuts::board::{get_score_kind, get_score_schema};
+use crate::shortcuts::exact::{get_exact_score, get_rank};
+use crate::shortcuts::names::get_display_names;
+use crate::shortcuts::redis::RedisConnectOr504;
+use crate::shortcuts::token::{Authorize, CheckBoardToken};
+use crate::shortcuts::webhooks::{emit_to, BoardEvent};
+use crate::utils::kebab::Skewer;
+use crate::utils::sorting::SortingOrder;
+use crate::config;
+
+
+/// Expected query params for [`GET /ban/`](route_ban_get).
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(crate) struct RouteBanListQuery {
+    /// The board to list the bans of, or [`None`] to list the global bans.
+    pub(crate) board: Option<String>,
+}
+
+
+/// Expected query params for [`PUT /ban/`](route_ban_put) and [`DELETE /ban/`](route_ban_delete).
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(crate) struct RouteBanQuery {
+    /// The board to ban the player from, or [`None`] to ban the player from all boards.
+    pub(crate) board: Option<String>,
+    /// The name of the player to ban.
+    pub(crate) player: String,
+}
+
+
+/// Expected body for [`PUT /ban/`](route_ban_put).
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(crate) struct RouteBanBody {
+    /// How severely the player should be banned.
+    pub(crate) kind: BanKind,
+    /// Why the player is being banned.
+    pub(crate) reason: String,
+}
+
+
+/// Determine the sorting order of `board`.
+async fn get_order(rconn: &mut redis::aio::Connection, board: &str) -> Result<SortingOrder, outcome::RequestTuple> {
+    let order_key = format!("board:{board}:order");
+
+    log::trace!("Determining sorting order...");
+    let order = rconn.get::<&str, Option<String>>(&order_key).await
+        .map_err(outcome::redis_cmd_failed)?
+        .ok_or_else(|| (StatusCode::NOT_FOUND, outcome::req_error!("No such board")))?;
+    let order = SortingOrder::try_from(order.as_str())
+        .map_err(|_| outcome::redis_unexpected_behaviour())?;
+    log::trace!("Sorting order is: {order:?}");
+
+    Ok(order)
+}
+
+
+/// Hide the score of `player` on `board`, notifying the live subscribers of its removal.
+async fn hide_score_and_emit(rconn: &mut redis::aio::Connection, board: &str, player: &str, order: SortingOrder) -> Result<(), outcome::RequestTuple> {
+    let kind = get_score_kind(rconn, board).await?;
+    let rank = get_rank(rconn, &format!("board:{board}:scores"), kind, order, player).await
+        .map_err(outcome::redis_cmd_failed)?;
+
+    let hidden = hide_score(rconn, board, player, order).await
+        .map_err(outcome::redis_cmd_failed)?;
+
+    if let (true, Some(rank)) = (hidden, rank) {
+        let mut pipe = redis::pipe();
+        emit_to(&mut pipe, board, BoardEvent::ScoreRemoved {name: player.to_string(), rank});
+        pipe.query_async::<redis::aio::Connection, ()>(rconn).await
+            .map_err(outcome::redis_cmd_failed)?;
+    }
+
+    Ok(())
+}
+
+
+/// Restore the hidden score of `player` on `board`, notifying the live subscribers of its return.
+async fn restore_score_and_emit(rconn: &mut redis::aio::Connection, board: &str, player: &str, order: SortingOrder) -> Result<(), outcome::RequestTuple> {
+    let restored = restore_score(rconn, board, player, order).await
+        .map_err(outcome::redis_cmd_failed)?;
+    if !restored {
+        return Ok(())
+    }
+
+    let scores_key = format!("board:{board}:scores");
+    let kind = get_score_kind(rconn, board).await?;
+    let schema = get_score_schema(rconn, board).await?;
+
+    let rank = get_rank(rconn, &scores_key, kind, order, player).await
+        .map_err(outcome::redis_cmd_failed)?
+        .ok_or_else(outcome::redis_unexpected_behaviour)?;
+    let score = rconn.zscore::<&str, &str, f64>(&scores_key, player).await
+        .map_err(outcome::redis_cmd_failed)?;
+    let display_name = get_display_names(rconn, board, None, &[player]).await
+        .map_err(outcome::redis_cmd_failed)?
+        .pop()
+        .unwrap_or_else(|| player.to_string());
+
+    let columns = schema.map(|schema| schema.decode(score, order));
+    let score = get_exact_score(rconn, &scores_key, kind, player, score).await
+        .map_err(outcome::redis_cmd_failed)?;
+
+    let mut pipe = redis::pipe();
+    emit_to(&mut pipe, board, BoardEvent::ScoreChanged {
+        name: player.to_string(),
+        display_name,
+        score,
+        columns,
+        rank,
+        previous_rank: None,
+    });
+    pipe.query_async::<redis::aio::Connection, ()>(rconn).await
+        .map_err(outcome::redis_cmd_failed)?;
+
+    Ok(())
+}
+
+
+/// Handler for `GET /ban/`.
+pub(crate) async fn route_ban_get(
+    // Redis client
+    Extension(rclient): Extension<redis::Client>,
+    // Request headers
+    headers: HeaderMap,
+    // Request query
+    Query(RouteBanListQuery {board}): Query<RouteBanListQuery>,
+) -> outcome::RequestResult {
+    let board = board.map(|board| board.to_kebab_lowercase());
+
+    let mut rconn = rclient.get_connection_or_504().await?;
+
+    let token = headers.get_authorization_or_401("Bearer")?;
+    rconn.check_manager_token_or_403(board.as_deref(), token).await?;
+
+    log::trace!("Retrieving bans...");
+    let bans = rconn.hgetall::<String, HashMap<String, String>>(bans_key(board.as_deref())).await
+        .map_err(outcome::redis_cmd_failed)?
+        .into_iter()
+        .map(|(player, ban)| serde_json::from_str::<BanObject>(&ban).map(|ban| (player, ban)))
+        .collect::<Result<HashMap<String, BanObject>, serde_json::Error>>()
+        .map_err(|_| outcome::redis_unexpected_behaviour())?;
+
+    Ok((StatusCode::OK, outcome::req_success!(bans)))
+}
+
+
+/// Handler for `PUT /ban/`.
+pub(crate) async fn route_ban_put(
+    // Redis client
+    Extension(rclient): Extension<redis::Client>,
+    // Client address
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    // Request headers
+    headers: HeaderMap,
+    // Request query
+    Query(RouteBanQuery {board, player}): Query<RouteBanQuery>,
+    // Request body
+    Json(RouteBanBody {kind, reason}): Json<RouteBanBody>,
+) -> outcome::RequestResult {
+    let board = board.map(|board| board.to_kebab_lowercase());
+    let player = player.to_kebab_lowercase_with(&config::PLAYER_NAMES);
+
+    log::trace!("Ensuring the reason is within limits...");
+    if reason.chars().count() > 1000 {
+        return Err((StatusCode::BAD_REQUEST, outcome::req_error!("Reason cannot be longer than 1000 characters")))
+    }
+
+    let mut rconn = rclient.get_connection_or_504().await?;
+
+    let token = headers.get_authorization_or_401("Bearer")?;
+    let actor = rconn.check_manager_token_or_403(board.as_deref(), token).await?;
+    let origin = Origin::new(actor, token, addr);
+
+    let order = match &board {
+        Some(board) => Some(get_order(&mut rconn, board).await?),
+        None => None,
+    };
+
+    let ban = BanObject {kind, reason, banned_at: chrono::Utc::now().timestamp()};
+
+    log::debug!("Banning {player:?} from {board:?}: {ban:?}");
+    let mut pipe = redis::pipe();
+    pipe.atomic();
+    pipe.hset(bans_key(board.as_deref()), &player, serde_json::to_string(&ban).expect("ban to be serializable")).ignore();
+    audit_to(&mut pipe, board.as_deref(), &origin, "PlayerBanned", &[
+        ("player", player.clone()),
+        ("kind", serde_json::to_string(&ban.kind).expect("ban kind to be serializable")),
+        ("reason", ban.reason.clone()),
+    ]);
+    pipe.query_async::<redis::aio::Connection, ()>(&mut rconn).await
+        .map_err(outcome::redis_cmd_failed)?;
+
+    match (&board, order, ban.kind) {
+        (Some(board), Some(order), BanKind::ShadowBan) => {
+            hide_score_and_emit(&mut rconn, board, &player, order).await?;
+        },
+        (None, _, BanKind::ShadowBan) => {
+            let boards = get_all_boards(&mut rconn).await
+                .map_err(outcome::redis_cmd_failed)?;
+            for board in boards {
+                // Boards may be deleted while they are being iterated on.
+                let order = match get_order(&mut rconn, &board).await {
+                    Ok(order) => order,
+                    Err(_) => continue,
+                };
+                hide_score_and_emit(&mut rconn, &board, &player, order).await?;
+            }
+        },
+        _ => {},
+    }
+
+    Ok((StatusCode::OK, outcome::req_success!(ban)))
+}
+
+
+/// Handler for `DELETE /ban/`.
+pub(crate) async fn route_ban_delete(
+    // Redis client
+    Extension(rclient): Extension<redis::Client>,
+    // Client address
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    // Request headers
+    headers: HeaderMap,
+    // Request query
+    Query(RouteBanQuery {board, player}): Query<RouteBanQuery>,
+) -> Result<StatusCode, outcome::RequestTuple> {
+    let board = board.map(|board| board.to_kebab_lowercase());
+    let player = player.to_kebab_lowercase_with(&config::PLAYER_NAMES);
+
+    let mut rconn = rclient.get_connection_or_504().await?;
+
+    let token = headers.get_authorization_or_401("Bearer")?;
+    let actor = rconn.check_manager_token_or_403(board.as_deref(), token).await?;
+    let origin = Origin::new(actor, token, addr);
+
+    log::debug!("Unbanning {player:?} from {board:?}...");
+    let removed = rconn.hdel::<String, &str, bool>(bans_key(board.as_deref()), &player).await
+        .map_err(outcome::redis_cmd_failed)?;
+
+    if !removed {
+        return Err((StatusCode::NOT_FOUND, outcome::req_error!("Player is not banned")))
+    }
+
+    let mut pipe = redis::pipe();
+    audit_to(&mut pipe, board.as_deref(), &origin, "PlayerUnbanned", &[
+        ("player", player.clone()),
+    ]);
+    pipe.query_async::<redis::aio::Connection, ()>(&mut rconn).await
+        .map_err(outcome::redis_cmd_failed)?;
+
+    let boards = match board {
+        Some(board) => vec![board],
+        None => get_all_boards(&mut rconn).await
+            .map_err(outcome::redis_cmd_failed)?,
+    };
+
+    for board in boards {
+        if get_ban_kind(&mut rconn, &board, &player).await?.is_none() {
+            // Boards may be deleted while they are being iterated on.
+            let order = match get_order(&mut rconn, &board).await {
+                Ok(order) => order,
+                Err(_) => continue,
+            };
+            restore_score_and_emit(&mut rconn, &board, &player, order).await?;
+        }
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,159 @@
+//! Module defining routes for `/score/batch/`.
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use axum::http::{HeaderMap, StatusCode};
+use axum::extract::{ConnectInfo, Extension, Json};
+use serde::Serialize;
+use serde::Deserialize;
+use serde_json::Value;
+use crate::outcome;
+use crate::routes::score::RouteScoreResponse;
+use crate::shortcuts::audit::Origin;
+use crate::shortcuts::blobs::SharedBlobStore;
+use crate::shortcuts::redis::RedisConnectOr504;
+use crate::shortcuts::submit::{get_submission_target, prepare_submission, write_submissions, Submission, SubmissionTarget};
+use crate::shortcuts::token::{Authorize, CheckBoardToken};
+use crate::utils::kebab::Skewer;
+use crate::utils::schema::ScoreValue;
+use crate::config;
+
+
+/// The maximum number of scores that can be submitted in a single batch.
+const MAX_BATCH_SIZE: usize = 500;
+
+
+/// A score to submit as part of [`POST /score/batch/`](route_score_batch_post).
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(crate) struct RouteScoreBatchEntry {
+    /// The board to submit the score to.
+    pub(crate) board: String,
+    /// The name of the player who set the score.
+    pub(crate) player: String,
+    /// The score to submit, with one value per column if the board has multiple.
+    pub(crate) score: ScoreValue,
+    /// The token to submit the score with, if different from the one in the `Authorization` header.
+    pub(crate) token: Option<String>,
+}
+
+
+/// The outcome of a score submitted as part of [`POST /score/batch/`](route_score_batch_post).
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "result")]
+pub(crate) enum RouteScoreBatchResult {
+    /// The score was stored, improving the previous one of the player, if any.
+    Improved(RouteScoreResponse),
+    /// The score was valid, but did not improve the previous one of the player.
+    Accepted(RouteScoreResponse),
+    /// The score was refused, for the same reason [`PUT /score/`](crate::routes::score::route_score_put) would have.
+    Rejected {
+        /// The status code [`PUT /score/`](crate::routes::score::route_score_put) would have responded with.
+        status: u16,
+        /// Why the score was refused.
+        error: Value,
+    },
+}
+
+impl From<outcome::RequestTuple> for RouteScoreBatchResult {
+    fn from((status, Json(error)): outcome::RequestTuple) -> Self {
+        Self::Rejected {status: status.as_u16(), error}
+    }
+}
+
+
+/// Handler for `POST /score/batch/`.
+///
+/// Checks every entry as [`PUT /score/`](crate::routes::score::route_score_put) would, then writes all the accepted ones together.
+///
+/// Results are returned in the same order as the entries.
+pub(crate) async fn route_score_batch_post(
+    // Redis client
+    Extension(rclient): Extension<redis::Client>,
+    // Blob store
+    Extension(blobs): Extension<SharedBlobStore>,
+    // Client address
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    // Request headers
+    headers: HeaderMap,
+    // Request body
+    Json(entries): Json<Vec<RouteScoreBatchEntry>>,
+) -> outcome::RequestResult {
+    log::trace!("Ensuring the batch is within limits...");
+    if entries.len() > MAX_BATCH_SIZE {
+        return Err((StatusCode::BAD_REQUEST, outcome::req_error!("Cannot submit more than 500 scores at once")))
+    }
+
+    let mut rconn = rclient.get_connection_or_504().await?;
+
+    let mut targets: HashMap<String, Result<SubmissionTarget, outcome::RequestTuple>> = HashMap::new();
+    let mut prepared: Vec<Result<(String, Submission), outcome::RequestTuple>> = vec![];
+
+    for RouteScoreBatchEntry {board, player, score, token} in entries {
+        let board = board.to_kebab_lowercase();
+        let display_name = player.clone();
+        let player = player.to_kebab_lowercase_with(&config::PLAYER_NAMES);
+
+        let submission = async {
+            log::trace!("Ensuring the player name follows the name policy...");
+            config::PLAYER_NAME_POLICY.check(&display_name, &player)
+                .map_err(|violation| (StatusCode::UNPROCESSABLE_ENTITY, outcome::req_error!((violation.message()))))?;
+
+            let token = match &token {
+                Some(token) => token.as_str(),
+                None => headers.get_authorization_or_401("Bearer")?,
+            };
+
+            let actor = rconn.check_board_or_player_token_or_403(&board, &player, token).await?;
+            let origin = Origin::new(actor, token, addr);
+
+            if !targets.contains_key(&board) {
+                let target = get_submission_target(&mut rconn, &board).await;
+                targets.insert(board.clone(), target);
+            }
+            let target = targets.get(&board)
+                .expect("target to have just been retrieved")
+                .clone()?;
+
+            let submission = prepare_submission(&mut rconn, &target, player, display_name, &score, origin).await?;
+            Ok((board, submission))
+        }.await;
+
+        prepared.push(submission);
+    }
+
+    let accepted: Vec<(&SubmissionTarget, &Submission)> = prepared.iter()
+        .filter_map(|submission| submission.as_ref().ok())
+        .map(|(board, submission)| {
+            let target = targets.get(board)
+                .and_then(|target| target.as_ref().ok())
+                .expect("target of an accepted submission to be valid");
+            (target, submission)
+        })
+        .collect();
+
+    log::debug!("Submitting {} of {} scores...", accepted.len(), prepared.len());
+    let mut written = write_submissions(&mut rconn, &*blobs, &accepted).await
+        .map_err(outcome::redis_cmd_failed)?
+        .into_iter();
+
+    let results = prepared.into_iter()
+        .map(|submission| match submission {
+            Ok((board, submission)) => {
+                let target = targets.get(&board)
+                    .and_then(|target| target.as_ref().ok())
+                    .expect("target of an accepted submission to be valid");
+                let written = written.next()
+                    .expect("every accepted submission to have been written");
+                let changed = written.changed;
+                let response = submission.into_response(target, written);
+                match changed {
+                    true => RouteScoreBatchResult::Improved(response),
+                    false => RouteScoreBatchResult::Accepted(response),
+                }
+            },
+            Err(error) => RouteScoreBatchResult::from(error),
+        })
+        .collect::<Vec<RouteScoreBatchResult>>();
+
+    Ok((StatusCode::OK, outcome::req_success!(results)))
+}
@@ -1,19 +1,38 @@
 //! Module defining routes for `/board/`.
 
+use std::net::SocketAddr;
 use axum::http::{HeaderMap, StatusCode};
-use axum::extract::{Extension, Json, Query};
+use axum::extract::{ConnectInfo, Extension, Json, Query};
 use redis::AsyncCommands;
 use serde::Serialize;
 use serde::Deserialize;
 use crate::outcome;
+use crate::routes::info::BoardMetadata;
+use crate::shortcuts::audit::{audit_to, Actor, Origin};
+use crate::shortcuts::board::{get_board_mode, get_score_kind, get_score_schema};
+use crate::shortcuts::exact::get_exact_scores;
 use crate::shortcuts::redis::RedisConnectOr504;
+use crate::shortcuts::season::archived_season_scores_key;
+use crate::shortcuts::names::{get_display_names, get_submitted_at};
+use crate::shortcuts::page::{get_page, PageStart};
+use crate::shortcuts::replays::{replays_key, ReplayObject};
 use crate::shortcuts::token::{Authorize, Generate};
+use crate::utils::auth::SubmissionAuth;
+use crate::utils::cursor::Cursor;
+use crate::utils::mode::BoardMode;
 use crate::utils::sorting::SortingOrder;
 use crate::utils::kebab::Skewer;
+use crate::utils::kind::{ScoreKind, ScoreNumber};
+use crate::utils::period::Period;
+use crate::utils::schema::ScoreSchema;
 use crate::utils::token::SecureToken;
 use crate::config;
 
 
+/// Header containing the [`Cursor`] to the next page returned by [`GET /board/`](route_board_get).
+pub(crate) const NEXT_CURSOR_HEADER: &str = "x-next-cursor";
+
+
 /// Expected body for [`POST /board/`](route_board_post).
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub(crate) struct RouteBoardBody {
@@ -21,6 +40,24 @@ pub(crate) struct RouteBoardBody {
     pub(crate) name: String,
     /// The [`SortingOrder`] of the scores in the board to create.
     pub(crate) order: SortingOrder,
+    /// The [`Period`]s over which scores should also be ranked separately.
+    #[serde(default)]
+    pub(crate) periods: Vec<Period>,
+    /// How submissions to the board to create are stored.
+    #[serde(default)]
+    pub(crate) mode: BoardMode,
+    /// Who is allowed to submit scores to the board to create.
+    #[serde(default)]
+    pub(crate) auth: SubmissionAuth,
+    /// Which numbers the board to create accepts as scores.
+    #[serde(default)]
+    pub(crate) kind: ScoreKind,
+    /// The columns making up the scores of the board to create, if they should be more than a single number.
+    #[serde(default)]
+    pub(crate) schema: Option<ScoreSchema>,
+    /// Human-readable information about the board to create.
+    #[serde(flatten)]
+    pub(crate) metadata: BoardMetadata,
 }
 
 
@@ -30,30 +67,106 @@ pub(crate) struct RouteBoardQuery {
     /// The name of the board to access.
     pub(crate) board: String,
     /// The offset to start returning scores from.
+    #[serde(default)]
     pub(crate) offset: usize,
+    /// The [`Cursor`] to continue returning scores from, overriding [`offset`](Self::offset).
+    pub(crate) cursor: Option<String>,
     /// How many scores to return.
     pub(crate) size: usize,
+    /// The lowest score to return, if any.
+    pub(crate) min: Option<f64>,
+    /// The highest score to return, if any.
+    pub(crate) max: Option<f64>,
+    /// Whether scores equal to [`min`](Self::min) should be excluded.
+    #[serde(default)]
+    pub(crate) min_exclusive: bool,
+    /// Whether scores equal to [`max`](Self::max) should be excluded.
+    #[serde(default)]
+    pub(crate) max_exclusive: bool,
+    /// The [`Period`] to return the scores of, instead of the all-time ones.
+    pub(crate) period: Option<Period>,
+    /// The archived season to return the scores of, instead of the live ones.
+    pub(crate) season: Option<String>,
 }
 
 
 /// A score set by a player, as a serializable struct.
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub(crate) struct ScoreObject {
-    /// The name of the player who set the score.
+    /// The normalized name of the player who set the score.
     pub(crate) name: String,
+    /// The name of the player who set the score, as it was originally submitted.
+    pub(crate) display_name: String,
     /// The score that the player set.
-    pub(crate) score: f64,
+    pub(crate) score: ScoreNumber,
+    /// The values of the columns of the score, if the board has multiple.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) columns: Option<Vec<f64>>,
+    /// Whether a replay proving the score can be retrieved with [`GET /score/replay/`](crate::routes::replay::route_score_replay_get).
+    #[serde(default)]
+    pub(crate) replay: bool,
+    /// When the entry was submitted, as a Unix timestamp, if it is a separate entry of a [`BoardMode::Initials`] board.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) submitted_at: Option<i64>,
 }
 
-impl From<(String, f64)> for ScoreObject {
-    fn from(t: (String, f64)) -> Self {
+impl From<((String, ScoreNumber), String)> for ScoreObject {
+    fn from(t: ((String, ScoreNumber), String)) -> Self {
         ScoreObject {
-            name: t.0,
-            score: t.1
+            name: t.0.0,
+            display_name: t.1,
+            score: t.0.1,
+            columns: None,
+            replay: false,
+            submitted_at: None,
         }
     }
 }
 
+impl ScoreObject {
+    /// Pair the given `(name, score)` tuples of `board`, or of its archived `season`, with the display names of their players, decoding their columns if the board has a [`ScoreSchema`].
+    ///
+    /// Entries are flagged as having a replay only if it was uploaded for the very score they have, which might not be the case on period leaderboards.
+    ///
+    /// Entries of [`BoardMode::Initials`] boards also report when they were submitted.
+    pub(crate) async fn from_scores(rconn: &mut redis::aio::Connection, board: &str, season: Option<&str>, mode: BoardMode, schema: Option<&ScoreSchema>, order: SortingOrder, scores: Vec<(String, ScoreNumber)>) -> Result<Vec<Self>, redis::RedisError> {
+        let players: Vec<&str> = scores.iter().map(|(name, _)| name.as_str()).collect();
+        let names = get_display_names(rconn, board, season, &players).await?;
+
+        let submitted = match mode {
+            BoardMode::Initials => get_submitted_at(rconn, board, season, &players).await?,
+            BoardMode::Players => vec![None; players.len()],
+        };
+
+        log::trace!("Checking which entries have a replay...");
+        let replays = match players.is_empty() {
+            true => vec![],
+            false => redis::cmd("HMGET").arg(replays_key(board, season)).arg(&players)
+                .query_async::<redis::aio::Connection, Vec<Option<String>>>(rconn).await?,
+        };
+
+        Ok(
+            scores.into_iter()
+                .zip(names)
+                .zip(replays)
+                .zip(submitted)
+                .map(|((score, replay), submitted_at)| {
+                    let score = Self::from(score);
+                    let replay = replay
+                        .and_then(|replay| serde_json::from_str::<ReplayObject>(&replay).ok())
+                        .is_some_and(|replay| replay.score == score.score);
+                    Self {
+                        columns: schema.map(|schema| schema.decode(score.score.as_f64(), order)),
+                        replay,
+                        submitted_at,
+                        ..score
+                    }
+                })
+                .collect()
+        )
+    }
+}
+
 
 /// Ensure that there is nothing stored at a certain Redis key.
 async fn ensure_key_is_empty(rconn: &mut redis::aio::Connection, key: &str) -> Result<(), outcome::RequestTuple> {
@@ -67,13 +180,22 @@ async fn ensure_key_is_empty(rconn: &mut redis::aio::Connection, key: &str) -> R
         .ok_or_else(|| (StatusCode::CONFLICT, outcome::req_error!("Board already exists")))
 }
 
+/// Format a score bound in the syntax expected by [Redis] for `BYSCORE` ranges.
+fn score_bound(value: Option<f64>, exclusive: bool, unbounded: &str) -> String {
+    match (value, exclusive) {
+        (None, _) => unbounded.to_string(),
+        (Some(value), false) => format!("{value}"),
+        (Some(value), true) => format!("({value}"),
+    }
+}
+
 /// Handler for `GET /board/`.
 pub(crate) async fn route_board_get(
     // Request query
-    Query(RouteBoardQuery {board, offset, size}): Query<RouteBoardQuery>,
+    Query(RouteBoardQuery {board, offset, cursor, size, min, max, min_exclusive, max_exclusive, period, season}): Query<RouteBoardQuery>,
     // Redis client
     Extension(rclient): Extension<redis::Client>,
-) -> outcome::RequestResult {
+) -> Result<(StatusCode, HeaderMap, Json<serde_json::Value>), outcome::RequestTuple> {
 
     let board = board.to_kebab_lowercase();
 
@@ -85,12 +207,46 @@ pub(crate) async fn route_board_get(
         ))
     }
 
+    log::trace!("Decoding cursor...");
+    let cursor = match cursor {
+        None => None,
+        Some(cursor) => Some(
+            Cursor::decode(&cursor)
+                .ok_or_else(|| (StatusCode::BAD_REQUEST, outcome::req_error!("Invalid cursor")))?
+        ),
+    };
+
     log::trace!("Determining the Redis key name...");
     let order_key = format!("board:{board}:order");
-    let scores_key = format!("board:{board}:scores");
+    let periods_key = format!("board:{board}:periods");
+    let mut scores_key = format!("board:{board}:scores");
 
     let mut rconn = rclient.get_connection_or_504().await?;
 
+    if let Some(period) = period {
+        log::trace!("Ensuring the board tracks the {period:?} period...");
+        let tracked = rconn.sismember::<&str, &str, bool>(&periods_key, period.into()).await
+            .map_err(outcome::redis_cmd_failed)?;
+        if !tracked {
+            return Err((StatusCode::NOT_FOUND, outcome::req_error!("Board does not track this period")))
+        }
+
+        let now = chrono::Utc::now().with_timezone(&*config::PERIODS_TIMEZONE);
+        scores_key = format!("board:{board}:scores:{}", period.id_at(&now));
+        log::trace!("Using the scores of the current period: {scores_key:?}");
+    }
+
+    let season = season.map(|season| season.to_kebab_lowercase());
+
+    if let Some(season) = &season {
+        if period.is_some() {
+            return Err((StatusCode::BAD_REQUEST, outcome::req_error!("Cannot request a period of an archived season")))
+        }
+
+        scores_key = archived_season_scores_key(&mut rconn, &board, season).await?;
+        log::trace!("Using the scores of the archived season: {scores_key:?}");
+    }
+
     log::trace!("Determining sorting order...");
     let order = rconn.get::<&str, String>(&order_key).await
         .map_err(outcome::redis_cmd_failed)?;
@@ -98,31 +254,75 @@ pub(crate) async fn route_board_get(
         .map_err(|_| outcome::redis_unexpected_behaviour())?;
     log::trace!("Sorting order is: {order:?}");
 
-    log::trace!("Building score retrieval command...");
-    let mut cmd = redis::Cmd::new();
-    let mut cmd_with_args = cmd.arg("ZRANGE").arg(&scores_key).arg(&offset).arg(offset + size);
-    if let SortingOrder::Descending = &order {
-        cmd_with_args = cmd_with_args.arg("REV");
+    let kind = get_score_kind(&mut rconn, &board).await?;
+    let mode = get_board_mode(&mut rconn, &board).await?;
+
+    let mut headers = HeaderMap::new();
+
+    let scores: Vec<(String, f64)> = if min.is_none() && max.is_none() {
+        let start = match cursor {
+            None => PageStart::Offset(offset),
+            Some(cursor) => PageStart::After(cursor),
+        };
+
+        log::trace!("Retrieving scores from {board}...");
+        let page = get_page(&mut rconn, &scores_key, kind, order, &start, size).await
+            .map_err(outcome::redis_cmd_failed)?;
+
+        if let Some(next) = page.next_cursor() {
+            log::trace!("Setting the cursor to the next page...");
+            headers.insert(
+                NEXT_CURSOR_HEADER,
+                next.encode().parse().expect("encoded cursor to be a valid header value")
+            );
+        }
+
+        page.scores
     }
-    cmd_with_args = cmd_with_args.arg("WITHSCORES");
+    else {
+        if cursor.is_some() {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                outcome::req_error!("Cannot use a cursor together with score bounds")
+            ))
+        }
 
-    log::trace!("Retrieving scores from {board}...");
-    let result: Vec<ScoreObject> = cmd_with_args
-        .query_async::<redis::aio::Connection, Vec<(String, f64)>>(&mut rconn).await
-        .map_err(outcome::redis_cmd_failed)?
-        .into_iter()
-        .map(From::<(String, f64)>::from)
-        .collect();
+        log::trace!("Building score retrieval command...");
+        let min = score_bound(min, min_exclusive, "-inf");
+        let max = score_bound(max, max_exclusive, "+inf");
+        let mut cmd = redis::Cmd::new();
+        let mut cmd_with_args = cmd.arg("ZRANGE").arg(&scores_key);
+        cmd_with_args = match &order {
+            SortingOrder::Ascending => cmd_with_args.arg(&min).arg(&max).arg("BYSCORE"),
+            SortingOrder::Descending => cmd_with_args.arg(&max).arg(&min).arg("BYSCORE").arg("REV"),
+        };
+        cmd_with_args = cmd_with_args.arg("LIMIT").arg(offset).arg(size).arg("WITHSCORES");
+
+        log::trace!("Retrieving scores from {board} between {min:?} and {max:?}...");
+        cmd_with_args
+            .query_async::<redis::aio::Connection, Vec<(String, f64)>>(&mut rconn).await
+            .map_err(outcome::redis_cmd_failed)?
+    };
+
+    let schema = get_score_schema(&mut rconn, &board).await?;
+
+    let scores = get_exact_scores(&mut rconn, &scores_key, kind, scores).await
+        .map_err(outcome::redis_cmd_failed)?;
+
+    log::trace!("Retrieving display names...");
+    let result = ScoreObject::from_scores(&mut rconn, &board, season.as_deref(), mode, schema.as_ref(), order, scores).await
+        .map_err(outcome::redis_cmd_failed)?;
 
-    Ok((StatusCode::OK, outcome::req_success!(result)))
+    Ok((StatusCode::OK, headers, outcome::req_success!(result)))
 }
 
 
 /// Handler for `POST /board/`.
 pub(crate) async fn route_board_post(
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
     headers: HeaderMap,
     Extension(rclient): Extension<redis::Client>,
-    Json(RouteBoardBody {name, order}): Json<RouteBoardBody>,
+    Json(RouteBoardBody {name, order, periods, mode, auth, kind, schema, mut metadata}): Json<RouteBoardBody>,
 ) -> outcome::RequestResult {
 
     let token = headers.get_authorization_or_401("Bearer")?;
@@ -130,6 +330,23 @@ pub(crate) async fn route_board_post(
         log::trace!("Token does not match, forbidding...");
         return Err((StatusCode::FORBIDDEN, outcome::req_error!("Invalid create token")))
     }
+    let origin = Origin::new(Actor::Creator, token, addr);
+
+    if metadata.title.is_none() {
+        log::trace!("Using the requested name as the board title...");
+        metadata.title = Some(name.clone());
+    }
+    metadata.ensure_valid_or_400()?;
+
+    if let Some(schema) = &schema {
+        log::trace!("Ensuring the score schema is valid...");
+        schema.check()
+            .map_err(|violation| (StatusCode::BAD_REQUEST, outcome::req_error!((violation.message()))))?;
+
+        if kind == ScoreKind::Integer {
+            return Err((StatusCode::BAD_REQUEST, outcome::req_error!("Boards with a score schema cannot also have integer scores")))
+        }
+    }
 
     let name = name.to_kebab_lowercase();
 
@@ -137,17 +354,29 @@ pub(crate) async fn route_board_post(
     let order_key = format!("board:{name}:order");
     let token_key = format!("board:{name}:token");
     let scores_key = format!("board:{name}:scores");
+    let periods_key = format!("board:{name}:periods");
+    let meta_key = format!("board:{name}:meta");
+    let mode_key = format!("board:{name}:mode");
+    let auth_key = format!("board:{name}:auth");
+    let kind_key = format!("board:{name}:kind");
+    let schema_key = format!("board:{name}:schema");
 
     let mut rconn = rclient.get_connection_or_504().await?;
 
     log::trace!("Watching board keys...");
-    redis::cmd("WATCH").arg(&order_key).arg(&token_key).arg(&scores_key).query_async(&mut rconn).await
+    redis::cmd("WATCH").arg(&order_key).arg(&token_key).arg(&scores_key).arg(&periods_key).arg(&meta_key).arg(&mode_key).arg(&auth_key).arg(&kind_key).arg(&schema_key).query_async(&mut rconn).await
         .map_err(outcome::redis_cmd_failed)?;
 
     log::trace!("Ensuring a board does not already exist...");
     ensure_key_is_empty(&mut rconn, &order_key).await?;
     ensure_key_is_empty(&mut rconn, &token_key).await?;
     ensure_key_is_empty(&mut rconn, &scores_key).await?;
+    ensure_key_is_empty(&mut rconn, &periods_key).await?;
+    ensure_key_is_empty(&mut rconn, &meta_key).await?;
+    ensure_key_is_empty(&mut rconn, &mode_key).await?;
+    ensure_key_is_empty(&mut rconn, &auth_key).await?;
+    ensure_key_is_empty(&mut rconn, &kind_key).await?;
+    ensure_key_is_empty(&mut rconn, &schema_key).await?;
 
     log::trace!("Starting Redis transaction...");
     redis::cmd("MULTI").query_async(&mut rconn).await
@@ -164,6 +393,42 @@ pub(crate) async fn route_board_post(
     log::trace!("Setting board token...");
     rconn.set(&token_key, &token.0).await
         .map_err(outcome::redis_cmd_failed)?;
+
+    log::trace!("Setting board mode...");
+    rconn.set(&mode_key, Into::<&str>::into(mode)).await
+        .map_err(outcome::redis_cmd_failed)?;
+
+    log::trace!("Setting board submission authentication...");
+    rconn.set(&auth_key, Into::<&str>::into(auth)).await
+        .map_err(outcome::redis_cmd_failed)?;
+
+    log::trace!("Setting board score kind...");
+    rconn.set(&kind_key, Into::<&str>::into(kind)).await
+        .map_err(outcome::redis_cmd_failed)?;
+
+    if let Some(schema) = &schema {
+        log::trace!("Setting board score schema...");
+        rconn.set(&schema_key, serde_json::to_string(schema).expect("schema to be serializable")).await
+            .map_err(outcome::redis_cmd_failed)?;
+    }
+
+    if !periods.is_empty() {
+        log::trace!("Setting board periods...");
+        rconn.sadd::<&str, Vec<&str>, ()>(&periods_key, periods.into_iter().map(Into::<&str>::into).collect::<Vec<&str>>()).await
+            .map_err(outcome::redis_cmd_failed)?;
+    }
+
+    log::trace!("Setting board metadata...");
+    let mut pipe = redis::pipe();
+    metadata.write_to(&mut pipe, &name);
+    audit_to(&mut pipe, Some(&name), &origin, "BoardCreated", &[
+        ("order", Into::<&str>::into(order).to_string()),
+        ("mode", Into::<&str>::into(mode).to_string()),
+        ("auth", Into::<&str>::into(auth).to_string()),
+        ("kind", Into::<&str>::into(kind).to_string()),
+    ]);
+    pipe.query_async::<redis::aio::Connection, ()>(&mut rconn).await
+        .map_err(outcome::redis_cmd_failed)?;
     
     log::trace!("Executing Redis transaction...");
     redis::cmd("EXEC").query_async(&mut rconn).await
@@ -0,0 +1,196 @@
+//! Module defining routes for `/board/export/`.
+
+use axum::body::StreamBody;
+use axum::http::StatusCode;
+use axum::http::header;
+use axum::extract::{Extension, Query};
+use axum::response::{IntoResponse, Response};
+use redis::AsyncCommands;
+use serde::Serialize;
+use serde::Deserialize;
+use crate::outcome;
+use crate::routes::board::ScoreObject;
+use crate::shortcuts::board::{get_board_mode, get_score_kind, get_score_schema};
+use crate::shortcuts::exact::get_exact_scores;
+use crate::shortcuts::page::{get_page, PageStart};
+use crate::shortcuts::redis::RedisConnectOr504;
+use crate::utils::kebab::Skewer;
+use crate::utils::kind::ScoreKind;
+use crate::utils::mode::BoardMode;
+use crate::utils::schema::ScoreSchema;
+use crate::utils::sorting::SortingOrder;
+
+
+/// How many scores are retrieved from Redis at a time while exporting.
+const EXPORT_PAGE_SIZE: usize = 1000;
+
+
+/// A format in which a board can be exported.
+#[derive(Copy, Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum ExportFormat {
+    /// One JSON [`ScoreObject`] per line.
+    #[default]
+    Ndjson,
+    /// Comma-separated values, with a header row.
+    Csv,
+}
+
+impl ExportFormat {
+    /// The `Content-Type` of the exported board.
+    fn content_type(&self) -> &'static str {
+        match self {
+            Self::Ndjson => "application/x-ndjson",
+            Self::Csv => "text/csv",
+        }
+    }
+
+    /// The text to emit before any score.
+    fn header(&self) -> &'static str {
+        match self {
+            Self::Ndjson => "",
+            Self::Csv => "name,display_name,score\r\n",
+        }
+    }
+
+    /// Format a single score as a line of the exported board.
+    fn line(&self, score: &ScoreObject) -> String {
+        match self {
+            Self::Ndjson => {
+                let mut line = serde_json::to_string(score).expect("score to be serializable");
+                line.push('\n');
+                line
+            },
+            Self::Csv => format!("{},{},{}\r\n", csv_field(&score.name), csv_field(&score.display_name), score.score),
+        }
+    }
+}
+
+
+/// Quote a CSV field if it contains special characters.
+fn csv_field(field: &str) -> String {
+    match field.contains([',', '"', '\r', '\n']) {
+        true => format!("\"{}\"", field.replace('"', "\"\"")),
+        false => field.to_string(),
+    }
+}
+
+
+/// Expected query params for [`GET /board/export/`](route_board_export_get).
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(crate) struct RouteBoardExportQuery {
+    /// The name of the board to export.
+    pub(crate) board: String,
+    /// The format to export the board in.
+    #[serde(default)]
+    pub(crate) format: ExportFormat,
+}
+
+
+/// State carried between the chunks of an export.
+struct ExportState {
+    rconn: redis::aio::Connection,
+    board: String,
+    scores_key: String,
+    order: SortingOrder,
+    kind: ScoreKind,
+    mode: BoardMode,
+    schema: Option<ScoreSchema>,
+    format: ExportFormat,
+    /// Where the next chunk should start from, or [`None`] if the export is complete.
+    next: Option<PageStart>,
+    /// Whether the [header](ExportFormat::header) has already been emitted.
+    started: bool,
+}
+
+
+/// Handler for `GET /board/export/`.
+pub(crate) async fn route_board_export_get(
+    // Request query
+    Query(RouteBoardExportQuery {board, format}): Query<RouteBoardExportQuery>,
+    // Redis client
+    Extension(rclient): Extension<redis::Client>,
+) -> Result<Response, outcome::RequestTuple> {
+
+    let board = board.to_kebab_lowercase();
+
+    log::trace!("Determining the Redis key names...");
+    let order_key = format!("board:{board}:order");
+    let scores_key = format!("board:{board}:scores");
+
+    let mut rconn = rclient.get_connection_or_504().await?;
+
+    log::trace!("Determining sorting order...");
+    let order = rconn.get::<&str, String>(&order_key).await
+        .map_err(outcome::redis_cmd_failed)?;
+    let order = SortingOrder::try_from(order.as_str())
+        .map_err(|_| outcome::redis_unexpected_behaviour())?;
+    log::trace!("Sorting order is: {order:?}");
+
+    let kind = get_score_kind(&mut rconn, &board).await?;
+    let mode = get_board_mode(&mut rconn, &board).await?;
+    let schema = get_score_schema(&mut rconn, &board).await?;
+
+    let state = ExportState {
+        rconn,
+        board: board.clone(),
+        scores_key,
+        order,
+        kind,
+        mode,
+        schema,
+        format,
+        next: Some(PageStart::Offset(0)),
+        started: false,
+    };
+
+    log::debug!("Exporting board {board:?} as {format:?}...");
+    let stream = futures::stream::unfold(state, |mut state| async move {
+        let start = state.next.take()?;
+
+        let page = match get_page(&mut state.rconn, &state.scores_key, state.kind, state.order, &start, EXPORT_PAGE_SIZE).await {
+            Ok(page) => page,
+            Err(err) => {
+                log::error!("{err:#?}");
+                return Some((Err(err), state))
+            }
+        };
+
+        state.next = page.next_cursor().map(PageStart::After);
+
+        let scores = match get_exact_scores(&mut state.rconn, &state.scores_key, state.kind, page.scores).await {
+            Ok(scores) => scores,
+            Err(err) => {
+                log::error!("{err:#?}");
+                state.next = None;
+                return Some((Err(err), state))
+            }
+        };
+
+        let scores = match ScoreObject::from_scores(&mut state.rconn, &state.board, None, state.mode, state.schema.as_ref(), state.order, scores).await {
+            Ok(scores) => scores,
+            Err(err) => {
+                log::error!("{err:#?}");
+                state.next = None;
+                return Some((Err(err), state))
+            }
+        };
+
+        let mut chunk = String::new();
+        if !state.started {
+            chunk.push_str(state.format.header());
+            state.started = true;
+        }
+        for score in scores {
+            chunk.push_str(&state.format.line(&score));
+        }
+
+        Some((Ok(chunk), state))
+    });
+
+    Ok((
+        StatusCode::OK,
+        [(header::CONTENT_TYPE, format.content_type())],
+        StreamBody::new(stream),
+    ).into_response())
+}
@@ -0,0 +1,225 @@
+//! Module defining routes for `/board/info/`.
+
+use std::net::SocketAddr;
+use axum::http::{HeaderMap, StatusCode};
+use axum::extract::{ConnectInfo, Extension, Json, Query};
+use redis::AsyncCommands;
+use serde::Serialize;
+use serde::Deserialize;
+use crate::outcome;
+use crate::shortcuts::audit::{audit_to, Actor, Origin};
+use crate::shortcuts::board::{get_board_mode, get_score_kind, get_score_schema};
+use crate::shortcuts::redis::RedisConnectOr504;
+use crate::shortcuts::state::{get_board_state, BoardState};
+use crate::shortcuts::token::{get_submission_auth, Authorize, CheckBoardToken};
+use crate::utils::auth::SubmissionAuth;
+use crate::utils::format::ScoreFormat;
+use crate::utils::kebab::Skewer;
+use crate::utils::kind::ScoreKind;
+use crate::utils::mode::BoardMode;
+use crate::utils::period::Period;
+use crate::utils::schema::ScoreSchema;
+use crate::utils::sorting::SortingOrder;
+
+
+/// Expected query params for `/board/info/` routes.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(crate) struct RouteBoardInfoQuery {
+    /// The name of the board to access.
+    pub(crate) board: String,
+}
+
+
+/// Human-readable information about a board, as a serializable struct.
+///
+/// Also used as the expected body for [`PUT /board/info/`](route_board_info_put).
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub(crate) struct BoardMetadata {
+    /// The name of the board to display to users.
+    pub(crate) title: Option<String>,
+    /// A description of the board.
+    pub(crate) description: Option<String>,
+    /// The unit of measure of the scores, such as `m` or `€`.
+    pub(crate) unit: Option<String>,
+    /// How the scores should be displayed.
+    pub(crate) format: Option<ScoreFormat>,
+}
+
+impl BoardMetadata {
+    /// Ensure that the metadata fields are within limits, returning `400 Bad Request` otherwise.
+    pub(crate) fn ensure_valid_or_400(&self) -> Result<(), outcome::RequestTuple> {
+        log::trace!("Ensuring the board metadata is within limits...");
+        if self.title.as_ref().map_or(0, |t| t.chars().count()) > 100 {
+            return Err((StatusCode::BAD_REQUEST, outcome::req_error!("Title cannot be longer than 100 characters")))
+        }
+        if self.description.as_ref().map_or(0, |d| d.chars().count()) > 1000 {
+            return Err((StatusCode::BAD_REQUEST, outcome::req_error!("Description cannot be longer than 1000 characters")))
+        }
+        if self.unit.as_ref().map_or(0, |u| u.chars().count()) > 20 {
+            return Err((StatusCode::BAD_REQUEST, outcome::req_error!("Unit cannot be longer than 20 characters")))
+        }
+        Ok(())
+    }
+
+    /// Queue the commands replacing the stored metadata of `board` with this one in the given pipeline.
+    pub(crate) fn write_to(&self, pipe: &mut redis::Pipeline, board: &str) {
+        let meta_key = format!("board:{board}:meta");
+
+        let mut fields: Vec<(&str, &str)> = vec![];
+        if let Some(title) = &self.title {
+            fields.push(("title", title));
+        }
+        if let Some(description) = &self.description {
+            fields.push(("description", description));
+        }
+        if let Some(unit) = &self.unit {
+            fields.push(("unit", unit));
+        }
+        if let Some(format) = self.format {
+            fields.push(("format", format.into()));
+        }
+
+        pipe.del(&meta_key).ignore();
+        if !fields.is_empty() {
+            pipe.hset_multiple(&meta_key, &fields).ignore();
+        }
+    }
+}
+
+
+/// Everything known about a board, as a serializable struct.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(crate) struct BoardInfo {
+    /// The name of the board, in kebab-case.
+    pub(crate) name: String,
+    /// The [`SortingOrder`] of the scores in the board.
+    pub(crate) order: SortingOrder,
+    /// The [`Period`]s tracked by the board.
+    pub(crate) periods: Vec<Period>,
+    /// How submissions to the board are stored.
+    pub(crate) mode: BoardMode,
+    /// Who is allowed to submit scores to the board.
+    pub(crate) auth: SubmissionAuth,
+    /// Which numbers the board accepts as scores.
+    pub(crate) kind: ScoreKind,
+    /// The columns making up the scores of the board, if it has more than a single number.
+    pub(crate) schema: Option<ScoreSchema>,
+    /// Whether the board is accepting submissions.
+    pub(crate) state: BoardState,
+    /// Human-readable information about the board.
+    #[serde(flatten)]
+    pub(crate) metadata: BoardMetadata,
+}
+
+
+/// Retrieve the [`BoardInfo`] of the given board.
+pub(crate) async fn get_board_info(rconn: &mut redis::aio::Connection, board: &str) -> Result<BoardInfo, outcome::RequestTuple> {
+    log::trace!("Determining the Redis key names...");
+    let order_key = format!("board:{board}:order");
+    let periods_key = format!("board:{board}:periods");
+    let meta_key = format!("board:{board}:meta");
+
+    log::trace!("Determining sorting order...");
+    let order = rconn.get::<&str, Option<String>>(&order_key).await
+        .map_err(outcome::redis_cmd_failed)?
+        .ok_or_else(|| (StatusCode::NOT_FOUND, outcome::req_error!("No such board")))?;
+    let order = SortingOrder::try_from(order.as_str())
+        .map_err(|_| outcome::redis_unexpected_behaviour())?;
+
+    log::trace!("Determining tracked periods...");
+    let periods = rconn.smembers::<&str, Vec<String>>(&periods_key).await
+        .map_err(outcome::redis_cmd_failed)?
+        .iter()
+        .map(|period| Period::try_from(period.as_str()))
+        .collect::<Result<Vec<Period>, ()>>()
+        .map_err(|_| outcome::redis_unexpected_behaviour())?;
+
+    let mode = get_board_mode(rconn, board).await?;
+
+    let auth = get_submission_auth(rconn, board).await?;
+
+    let kind = get_score_kind(rconn, board).await?;
+
+    let schema = get_score_schema(rconn, board).await?;
+
+    let state = get_board_state(rconn, board).await?;
+
+    log::trace!("Retrieving metadata...");
+    let (title, description, unit, format) = redis::cmd("HMGET").arg(&meta_key).arg("title").arg("description").arg("unit").arg("format")
+        .query_async::<redis::aio::Connection, (Option<String>, Option<String>, Option<String>, Option<String>)>(rconn).await
+        .map_err(outcome::redis_cmd_failed)?;
+    let format = format
+        .map(|format| ScoreFormat::try_from(format.as_str()))
+        .transpose()
+        .map_err(|_| outcome::redis_unexpected_behaviour())?;
+
+    Ok(BoardInfo {
+        name: board.to_string(),
+        order,
+        periods,
+        mode,
+        auth,
+        kind,
+        schema,
+        state,
+        metadata: BoardMetadata {title, description, unit, format},
+    })
+}
+
+
+/// Handler for `GET /board/info/`.
+pub(crate) async fn route_board_info_get(
+    // Request query
+    Query(RouteBoardInfoQuery {board}): Query<RouteBoardInfoQuery>,
+    // Redis client
+    Extension(rclient): Extension<redis::Client>,
+) -> outcome::RequestResult {
+
+    let board = board.to_kebab_lowercase();
+
+    let mut rconn = rclient.get_connection_or_504().await?;
+
+    let result = get_board_info(&mut rconn, &board).await?;
+
+    Ok((StatusCode::OK, outcome::req_success!(result)))
+}
+
+
+/// Handler for `PUT /board/info/`.
+pub(crate) async fn route_board_info_put(
+    // Redis client
+    Extension(rclient): Extension<redis::Client>,
+    // Client address
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    // Request headers
+    headers: HeaderMap,
+    // Request query
+    Query(RouteBoardInfoQuery {board}): Query<RouteBoardInfoQuery>,
+    // Request body
+    Json(metadata): Json<BoardMetadata>,
+) -> outcome::RequestResult {
+
+    let board = board.to_kebab_lowercase();
+
+    metadata.ensure_valid_or_400()?;
+
+    let token = headers.get_authorization_or_401("Bearer")?;
+    let mut rconn = rclient.get_connection_or_504().await?;
+
+    rconn.check_board_token_or_403(&board, token).await?;
+    let origin = Origin::new(Actor::Board, token, addr);
+
+    log::debug!("Setting the metadata of {board:?} to {metadata:?}");
+    let mut pipe = redis::pipe();
+    pipe.atomic();
+    metadata.write_to(&mut pipe, &board);
+    audit_to(&mut pipe, Some(&board), &origin, "MetadataChanged", &[
+        ("metadata", serde_json::to_string(&metadata).expect("metadata to be serializable")),
+    ]);
+    pipe.query_async::<redis::aio::Connection, ()>(&mut rconn).await
+        .map_err(outcome::redis_cmd_failed)?;
+
+    let result = get_board_info(&mut rconn, &board).await?;
+
+    Ok((StatusCode::OK, outcome::req_success!(result)))
+}
@@ -0,0 +1,263 @@
+//! Module defining routes for `/board/live/`.
+
+use std::convert::Infallible;
+use axum::http::StatusCode;
+use axum::extract::{Extension, Query};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use futures::Stream;
+use tokio::sync::broadcast::error::RecvError;
+use redis::AsyncCommands;
+use serde::Serialize;
+use serde::Deserialize;
+use crate::outcome;
+use crate::routes::around::RankedScoreObject;
+use crate::shortcuts::board::{get_board_mode, get_score_kind, get_score_schema};
+use crate::shortcuts::exact::{get_exact_score, get_rank};
+use crate::shortcuts::names::get_display_names;
+use crate::shortcuts::redis::RedisConnectOr504;
+use crate::shortcuts::live::{LiveReceiver, SharedLiveHub};
+use crate::shortcuts::webhooks::{BoardEvent, QueuedEvent};
+use crate::utils::kebab::Skewer;
+use crate::utils::kind::ScoreKind;
+use crate::utils::mode::BoardMode;
+use crate::utils::schema::ScoreSchema;
+use crate::utils::sorting::SortingOrder;
+use crate::config;
+
+
+/// Expected query params for [`GET /board/live/`](route_board_live_get).
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(crate) struct RouteBoardLiveQuery {
+    /// The name of the board to subscribe to.
+    pub(crate) board: String,
+    /// Only receive updates about the first `top` positions of the board.
+    pub(crate) top: Option<usize>,
+    /// Only receive updates about the given player.
+    pub(crate) player: Option<String>,
+}
+
+
+/// Which updates a live subscriber is interested in.
+#[derive(Clone, Debug)]
+enum LiveFilter {
+    /// Every update.
+    All,
+    /// Updates about the first positions of the board.
+    Top(usize),
+    /// Updates about a single player, along with the rank it was last known to have.
+    Player(String, Option<usize>),
+}
+
+
+/// The state of a live subscription, carried across the items of its stream.
+struct LiveSubscription {
+    /// The events of the board, received through the Pub/Sub connection it shares with the other subscribers.
+    receiver: LiveReceiver,
+    /// A connection to query the board with, separate from the one in Pub/Sub mode, only kept by [`LiveFilter::Player`] subscriptions to check the rank of their player.
+    rconn: Option<redis::aio::Connection>,
+    /// The board subscribed to.
+    board: String,
+    /// The sorting order of the board.
+    order: SortingOrder,
+    /// Which numbers the board accepts as scores.
+    kind: ScoreKind,
+    /// The columns making up the scores of the board, if it has more than a single number.
+    schema: Option<ScoreSchema>,
+    /// Which updates should be sent.
+    filter: LiveFilter,
+}
+
+
+impl LiveSubscription {
+    /// Determine the [`Event`] to send for `queued`, if any.
+    async fn handle(&mut self, queued: QueuedEvent) -> Result<Option<Event>, redis::RedisError> {
+        let forward = |queued: &QueuedEvent| {
+            let name = match queued.event {
+                BoardEvent::ScoreImproved {..} => "ScoreImproved",
+                BoardEvent::ScoreChanged {..} => "ScoreChanged",
+                BoardEvent::ScoreRemoved {..} => "ScoreRemoved",
+                BoardEvent::BoardReset {..} => "BoardReset",
+            };
+            Event::default().event(name).json_data(queued).expect("event to be serializable")
+        };
+
+        match (&mut self.filter, &queued.event) {
+            (LiveFilter::Player(_, last), BoardEvent::BoardReset {..}) => {
+                *last = None;
+                Ok(Some(forward(&queued)))
+            },
+            (_, BoardEvent::BoardReset {..}) => Ok(Some(forward(&queued))),
+            (LiveFilter::All, _) => Ok(Some(forward(&queued))),
+            (LiveFilter::Top(top), BoardEvent::ScoreImproved {rank, ..} | BoardEvent::ScoreRemoved {rank, ..}) => Ok((*rank < *top).then(|| forward(&queued))),
+            (LiveFilter::Top(top), BoardEvent::ScoreChanged {rank, previous_rank, ..}) => {
+                let entered = *rank < *top;
+                let left = previous_rank.map(|previous| previous < *top).unwrap_or(false);
+                Ok((entered || left).then(|| forward(&queued)))
+            },
+            (LiveFilter::Player(player, last), BoardEvent::ScoreImproved {name, rank, ..} | BoardEvent::ScoreChanged {name, rank, ..}) if name == player => {
+                *last = Some(*rank);
+                Ok(Some(forward(&queued)))
+            },
+            (LiveFilter::Player(player, last), BoardEvent::ScoreRemoved {name, ..}) if name == player => {
+                *last = None;
+                Ok(Some(forward(&queued)))
+            },
+            (LiveFilter::Player(_, last), BoardEvent::ScoreImproved {rank, ..}) => {
+                // Only scores reaching the position of the player or above can push them down.
+                match *last {
+                    Some(previous) if *rank <= previous => self.check_rank().await,
+                    _ => Ok(None),
+                }
+            },
+            (LiveFilter::Player(_, last), BoardEvent::ScoreChanged {..} | BoardEvent::ScoreRemoved {..}) => {
+                // Corrected and removed scores can move the player either way.
+                match *last {
+                    Some(_) => self.check_rank().await,
+                    None => Ok(None),
+                }
+            },
+        }
+    }
+
+    /// Check whether the rank of the subscribed player changed because of the score of another player, returning the `RankChanged` [`Event`] to send if it did.
+    async fn check_rank(&mut self) -> Result<Option<Event>, redis::RedisError> {
+        let (player, last) = match &mut self.filter {
+            LiveFilter::Player(player, last) => (player.clone(), last),
+            _ => return Ok(None),
+        };
+        let rconn = match &mut self.rconn {
+            Some(rconn) => rconn,
+            None => return Ok(None),
+        };
+
+        log::trace!("Checking whether the rank of {player:?} changed...");
+        let scores_key = format!("board:{}:scores", self.board);
+        let current = get_rank(rconn, &scores_key, self.kind, self.order, &player).await?;
+        if current == *last {
+            return Ok(None)
+        }
+        *last = current;
+
+        let current = match current {
+            Some(current) => current,
+            None => return Ok(None),
+        };
+
+        let score = rconn.zscore::<&str, &str, f64>(&scores_key, &player).await?;
+        let display_name = get_display_names(rconn, &self.board, None, &[&player]).await?
+            .pop()
+            .unwrap_or_else(|| player.clone());
+
+        let columns = self.schema.as_ref().map(|schema| schema.decode(score, self.order));
+        let score = get_exact_score(rconn, &scores_key, self.kind, &player, score).await?;
+        let update = RankedScoreObject {rank: current, name: player, display_name, score, columns};
+        Ok(Some(Event::default().event("RankChanged").json_data(&update).expect("update to be serializable")))
+    }
+
+    /// Wait for the next [`Event`] to send, returning [`None`] if the subscription ended.
+    async fn next_event(&mut self) -> Option<Event> {
+        loop {
+            let payload = match self.receiver.events.recv().await {
+                Ok(payload) => payload,
+                Err(RecvError::Lagged(skipped)) => {
+                    log::warn!("Live subscriber of {:?} was too slow and missed {skipped} events", self.board);
+                    continue
+                },
+                Err(RecvError::Closed) => return None,
+            };
+
+            let queued = match serde_json::from_str::<QueuedEvent>(&payload) {
+                Ok(queued) => queued,
+                Err(_) => {
+                    log::warn!("Discarding malformed event of {:?}", self.board);
+                    continue
+                }
+            };
+
+            match self.handle(queued).await {
+                Ok(Some(event)) => return Some(event),
+                Ok(None) => continue,
+                Err(err) => {
+                    log::error!("{err:#?}");
+                    return None
+                }
+            }
+        }
+    }
+}
+
+
+/// Handler for `GET /board/live/`.
+///
+/// Streams the updates of a board as [Server-Sent Events](https://html.spec.whatwg.org/multipage/server-sent-events.html), until the client disconnects.
+pub(crate) async fn route_board_live_get(
+    // Request query
+    Query(RouteBoardLiveQuery {board, top, player}): Query<RouteBoardLiveQuery>,
+    // Redis client
+    Extension(rclient): Extension<redis::Client>,
+    // Live subscriptions
+    Extension(live): Extension<SharedLiveHub>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, outcome::RequestTuple> {
+    let board = board.to_kebab_lowercase();
+    let player = player.map(|player| player.to_kebab_lowercase_with(&config::PLAYER_NAMES));
+
+    log::trace!("Determining the Redis key names...");
+    let order_key = format!("board:{board}:order");
+
+    let mut rconn = rclient.get_connection_or_504().await?;
+
+    log::trace!("Determining sorting order...");
+    let order = rconn.get::<&str, Option<String>>(&order_key).await
+        .map_err(outcome::redis_cmd_failed)?
+        .ok_or_else(|| (StatusCode::NOT_FOUND, outcome::req_error!("No such board")))?;
+    let order = SortingOrder::try_from(order.as_str())
+        .map_err(|_| outcome::redis_unexpected_behaviour())?;
+    log::trace!("Sorting order is: {order:?}");
+
+    let kind = get_score_kind(&mut rconn, &board).await?;
+    let schema = get_score_schema(&mut rconn, &board).await?;
+
+    let filter = match (top, player) {
+        (Some(_), Some(_)) => {
+            return Err((StatusCode::BAD_REQUEST, outcome::req_error!("Cannot subscribe to both a top window and a player")))
+        },
+        (Some(0), None) => {
+            return Err((StatusCode::BAD_REQUEST, outcome::req_error!("Top must be at least 1")))
+        },
+        (Some(top), None) => LiveFilter::Top(top),
+        (None, Some(player)) => {
+            if let BoardMode::Initials = get_board_mode(&mut rconn, &board).await? {
+                return Err((StatusCode::BAD_REQUEST, outcome::req_error!("Board does not track players")))
+            }
+            let rank = get_rank(&mut rconn, &format!("board:{board}:scores"), kind, order, &player).await
+                .map_err(outcome::redis_cmd_failed)?;
+            LiveFilter::Player(player, rank)
+        },
+        (None, None) => LiveFilter::All,
+    };
+
+    let rconn = match filter {
+        LiveFilter::Player(..) => Some(rconn),
+        _ => None,
+    };
+
+    log::debug!("Subscribing to {board:?} with {filter:?}...");
+    let receiver = live.subscribe_or_503(&board).await?;
+
+    let subscription = LiveSubscription {
+        receiver,
+        rconn,
+        board,
+        order,
+        kind,
+        schema,
+        filter,
+    };
+
+    let stream = futures::stream::unfold(subscription, |mut subscription| async move {
+        let event = subscription.next_event().await?;
+        Some((Ok(event), subscription))
+    });
+
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
@@ -1,3 +1,19 @@
 pub(crate) mod home;
 pub(crate) mod board;
-pub(crate) mod score;
\ No newline at end of file
+pub(crate) mod score;
+pub(crate) mod around;
+pub(crate) mod rank;
+pub(crate) mod export;
+pub(crate) mod stats;
+pub(crate) mod season;
+pub(crate) mod state;
+pub(crate) mod info;
+pub(crate) mod player;
+pub(crate) mod moderation;
+pub(crate) mod ban;
+pub(crate) mod audit;
+pub(crate) mod webhooks;
+pub(crate) mod live;
+pub(crate) mod batch;
+pub(crate) mod sync;
+pub(crate) mod replay;
\ No newline at end of file
@@ -0,0 +1,301 @@
+//! Module defining routes for `/score/moderation/`.
+
+use std::net::SocketAddr;
+use axum::http::{HeaderMap, StatusCode};
+use axum::extract::{ConnectInfo, Extension, Json, Query};
+use redis::AsyncCommands;
+use serde::Serialize;
+use serde::Deserialize;
+use crate::outcome;
+use crate::routes::board::ScoreObject;
+use crate::routes::score::RouteScoreResponse;
+use crate::shortcuts::audit::{audit_to, Origin};
+use crate::shortcuts::blobs::SharedBlobStore;
+use crate::shortcuts::board::{get_board_mode, get_score_kind, get_score_schema};
+use crate::shortcuts::exact::{exact_scores_key, get_exact_score, get_rank, insert_score, sum_scores_key};
+use crate::shortcuts::names::{get_display_names, get_submitted_at, names_key, submitted_key};
+use crate::shortcuts::redis::RedisConnectOr504;
+use crate::shortcuts::replays::{delete_replays, detach_replay_to, metadata_key, replays_key};
+use crate::shortcuts::submit::score_of_or_422;
+use crate::shortcuts::token::{Authorize, CheckBoardToken};
+use crate::shortcuts::webhooks::{emit_to, BoardEvent};
+use crate::utils::kebab::Skewer;
+use crate::utils::mode::BoardMode;
+use crate::utils::period::Period;
+use crate::utils::schema::ScoreValue;
+use crate::utils::sorting::SortingOrder;
+use crate::config;
+
+
+/// Expected query params for `/score/moderation/` routes.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(crate) struct RouteScoreModerationQuery {
+    /// The board to moderate.
+    pub(crate) board: String,
+    /// The name of the player whose score should be moderated.
+    pub(crate) player: String,
+}
+
+
+/// Expected body for [`DELETE /score/moderation/`](route_score_moderation_delete).
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(crate) struct RouteScoreModerationDeleteBody {
+    /// Why the score is being removed, to be recorded in the audit log.
+    pub(crate) reason: String,
+}
+
+
+/// Expected body for [`PUT /score/moderation/`](route_score_moderation_put).
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(crate) struct RouteScoreModerationPutBody {
+    /// The corrected score of the player, with one value per column if the board has multiple.
+    pub(crate) score: ScoreValue,
+    /// Why the score is being corrected, to be recorded in the audit log.
+    pub(crate) reason: String,
+}
+
+
+/// Retrieve the keys of the period leaderboards of `board` that are currently being filled.
+async fn current_period_keys(rconn: &mut redis::aio::Connection, board: &str) -> Result<Vec<String>, outcome::RequestTuple> {
+    let periods_key = format!("board:{board}:periods");
+
+    log::trace!("Determining tracked periods...");
+    let periods = rconn.smembers::<&str, Vec<String>>(&periods_key).await
+        .map_err(outcome::redis_cmd_failed)?;
+
+    let now = chrono::Utc::now().with_timezone(&*config::PERIODS_TIMEZONE);
+    periods.iter()
+        .map(|period| {
+            let period = Period::try_from(period.as_str())
+                .map_err(|_| outcome::redis_unexpected_behaviour())?;
+            Ok(format!("board:{board}:scores:{}", period.id_at(&now)))
+        })
+        .collect()
+}
+
+
+/// Ensure that the reason of a moderation action is within limits, returning `400 Bad Request` otherwise.
+fn ensure_reason_is_valid_or_400(reason: &str) -> Result<(), outcome::RequestTuple> {
+    log::trace!("Ensuring the reason is within limits...");
+    if reason.trim().is_empty() {
+        return Err((StatusCode::BAD_REQUEST, outcome::req_error!("Reason cannot be empty")))
+    }
+    if reason.chars().count() > 1000 {
+        return Err((StatusCode::BAD_REQUEST, outcome::req_error!("Reason cannot be longer than 1000 characters")))
+    }
+    Ok(())
+}
+
+
+/// Handler for `DELETE /score/moderation/`.
+///
+/// The metadata and the replay of the entry are removed along with it.
+pub(crate) async fn route_score_moderation_delete(
+    // Redis client
+    Extension(rclient): Extension<redis::Client>,
+    // Blob store
+    Extension(blobs): Extension<SharedBlobStore>,
+    // Client address
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    // Request headers
+    headers: HeaderMap,
+    // Request query
+    Query(RouteScoreModerationQuery {board, player}): Query<RouteScoreModerationQuery>,
+    // Request body
+    Json(RouteScoreModerationDeleteBody {reason}): Json<RouteScoreModerationDeleteBody>,
+) -> outcome::RequestResult {
+    let board = board.to_kebab_lowercase();
+    let player = player.to_kebab_lowercase_with(&config::PLAYER_NAMES);
+
+    ensure_reason_is_valid_or_400(&reason)?;
+
+    log::trace!("Determining the Redis key names...");
+    let order_key = format!("board:{board}:order");
+    let scores_key = format!("board:{board}:scores");
+
+    let token = headers.get_authorization_or_401("Bearer")?;
+    let mut rconn = rclient.get_connection_or_504().await?;
+
+    let actor = rconn.check_board_or_admin_token_or_403(&board, token).await?;
+    let origin = Origin::new(actor, token, addr);
+
+    log::trace!("Getting the current score...");
+    let score = rconn.zscore::<&str, &str, Option<f64>>(&scores_key, &player).await
+        .map_err(outcome::redis_cmd_failed)?
+        .ok_or_else(|| (StatusCode::NOT_FOUND, outcome::req_error!("Player has no score on this board")))?;
+    log::trace!("Score is: {score:?}");
+
+    let display_name = get_display_names(&mut rconn, &board, None, &[&player]).await
+        .map_err(outcome::redis_cmd_failed)?
+        .pop()
+        .ok_or_else(outcome::redis_unexpected_behaviour)?;
+
+    log::trace!("Determining sorting order...");
+    let order = rconn.get::<&str, String>(&order_key).await
+        .map_err(outcome::redis_cmd_failed)?;
+    let order = SortingOrder::try_from(order.as_str())
+        .map_err(|_| outcome::redis_unexpected_behaviour())?;
+    log::trace!("Sorting order is: {order:?}");
+
+    let kind = get_score_kind(&mut rconn, &board).await?;
+    let mode = get_board_mode(&mut rconn, &board).await?;
+    let schema = get_score_schema(&mut rconn, &board).await?;
+
+    let columns = schema.map(|schema| schema.decode(score, order));
+    let score = get_exact_score(&mut rconn, &scores_key, kind, &player, score).await
+        .map_err(outcome::redis_cmd_failed)?;
+
+    let submitted_at = match mode {
+        BoardMode::Initials => get_submitted_at(&mut rconn, &board, None, &[&player]).await
+            .map_err(outcome::redis_cmd_failed)?
+            .pop()
+            .ok_or_else(outcome::redis_unexpected_behaviour)?,
+        BoardMode::Players => None,
+    };
+
+    let rank = get_rank(&mut rconn, &scores_key, kind, order, &player).await
+        .map_err(outcome::redis_cmd_failed)?
+        .ok_or_else(outcome::redis_unexpected_behaviour)?;
+    log::trace!("Rank is: {rank:?}");
+
+    let period_keys = current_period_keys(&mut rconn, &board).await?;
+
+    log::debug!("Removing the score of {player:?} from {board:?}: {reason:?}");
+    let mut pipe = redis::pipe();
+    pipe.atomic();
+    pipe.zrem(&scores_key, &player).ignore();
+    pipe.hdel(exact_scores_key(&scores_key), &player).ignore();
+    pipe.del(sum_scores_key(&scores_key)).ignore();
+    for period_key in period_keys.iter() {
+        pipe.zrem(period_key, &player).ignore();
+        pipe.hdel(exact_scores_key(period_key), &player).ignore();
+    }
+    pipe.hdel(names_key(&board, None), &player).ignore();
+    pipe.hdel(submitted_key(&board, None), &player).ignore();
+    pipe.hdel(metadata_key(&board, None), &player).ignore();
+    detach_replay_to(&mut pipe, &replays_key(&board, None), &player);
+    audit_to(&mut pipe, Some(&board), &origin, "ScoreDeleted", &[
+        ("player", player.clone()),
+        ("previous", score.to_string()),
+        ("reason", reason),
+    ]);
+    emit_to(&mut pipe, &board, BoardEvent::ScoreRemoved {name: player.clone(), rank});
+    let detached = pipe.query_async::<redis::aio::Connection, Vec<Option<String>>>(&mut rconn).await
+        .map_err(outcome::redis_cmd_failed)?;
+    delete_replays(&*blobs, detached).await;
+
+    let result = ScoreObject {name: player, display_name, score, columns, replay: false, submitted_at};
+
+    Ok((StatusCode::OK, outcome::req_success!(result)))
+}
+
+
+/// Handler for `PUT /score/moderation/`.
+///
+/// Unlike [`PUT /score/`](crate::routes::score::route_score_put), the score is replaced even if it is worse than the current one.
+///
+/// The metadata and the replay of the entry are removed, as they do not describe the corrected score.
+pub(crate) async fn route_score_moderation_put(
+    // Redis client
+    Extension(rclient): Extension<redis::Client>,
+    // Blob store
+    Extension(blobs): Extension<SharedBlobStore>,
+    // Client address
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    // Request headers
+    headers: HeaderMap,
+    // Request query
+    Query(RouteScoreModerationQuery {board, player}): Query<RouteScoreModerationQuery>,
+    // Request body
+    Json(RouteScoreModerationPutBody {score, reason}): Json<RouteScoreModerationPutBody>,
+) -> outcome::RequestResult {
+    let board = board.to_kebab_lowercase();
+    let player = player.to_kebab_lowercase_with(&config::PLAYER_NAMES);
+
+    ensure_reason_is_valid_or_400(&reason)?;
+
+    log::trace!("Determining the Redis key names...");
+    let order_key = format!("board:{board}:order");
+    let scores_key = format!("board:{board}:scores");
+
+    let token = headers.get_authorization_or_401("Bearer")?;
+    let mut rconn = rclient.get_connection_or_504().await?;
+
+    let actor = rconn.check_board_or_admin_token_or_403(&board, token).await?;
+    let origin = Origin::new(actor, token, addr);
+
+    log::trace!("Determining sorting order...");
+    let order = rconn.get::<&str, Option<String>>(&order_key).await
+        .map_err(outcome::redis_cmd_failed)?
+        .ok_or_else(|| (StatusCode::NOT_FOUND, outcome::req_error!("No such board")))?;
+    let order = SortingOrder::try_from(order.as_str())
+        .map_err(|_| outcome::redis_unexpected_behaviour())?;
+    log::trace!("Sorting order is: {order:?}");
+
+    let kind = get_score_kind(&mut rconn, &board).await?;
+    let schema = get_score_schema(&mut rconn, &board).await?;
+    let score = score_of_or_422(schema.as_ref(), kind, order, &score)?;
+
+    log::trace!("Ensuring the score is a number...");
+    if !score.as_f64().is_finite() {
+        return Err((StatusCode::BAD_REQUEST, outcome::req_error!("Score must be a finite number")))
+    }
+
+    log::trace!("Getting the current score...");
+    let previous = rconn.zscore::<&str, &str, Option<f64>>(&scores_key, &player).await
+        .map_err(outcome::redis_cmd_failed)?
+        .ok_or_else(|| (StatusCode::NOT_FOUND, outcome::req_error!("Player has no score on this board")))?;
+    let previous = get_exact_score(&mut rconn, &scores_key, kind, &player, previous).await
+        .map_err(outcome::redis_cmd_failed)?;
+    log::trace!("Score is: {previous:?}");
+
+    let previous_rank = get_rank(&mut rconn, &scores_key, kind, order, &player).await
+        .map_err(outcome::redis_cmd_failed)?;
+
+    let period_keys = current_period_keys(&mut rconn, &board).await?;
+
+    log::debug!("Overriding the score of {player:?} on {board:?} with {score:?}: {reason:?}");
+    let scores_keys: Vec<String> = std::iter::once(scores_key.clone())
+        .chain(period_keys)
+        .collect();
+    insert_score(&mut rconn, &scores_keys, "XX", score, &player).await
+        .map_err(outcome::redis_cmd_failed)?;
+
+    let rank = get_rank(&mut rconn, &scores_key, kind, order, &player).await
+        .map_err(outcome::redis_cmd_failed)?
+        .ok_or_else(outcome::redis_unexpected_behaviour)?;
+    log::trace!("Rank is: {rank:?}");
+
+    let display_name = get_display_names(&mut rconn, &board, None, &[&player]).await
+        .map_err(outcome::redis_cmd_failed)?
+        .pop()
+        .ok_or_else(outcome::redis_unexpected_behaviour)?;
+
+    let columns = schema.map(|schema| schema.decode(score.as_f64(), order));
+
+    let mut pipe = redis::pipe();
+    pipe.atomic();
+    pipe.hdel(metadata_key(&board, None), &player).ignore();
+    detach_replay_to(&mut pipe, &replays_key(&board, None), &player);
+    audit_to(&mut pipe, Some(&board), &origin, "ScoreOverridden", &[
+        ("player", player.clone()),
+        ("previous", previous.to_string()),
+        ("score", score.to_string()),
+        ("reason", reason),
+    ]);
+    emit_to(&mut pipe, &board, BoardEvent::ScoreChanged {
+        name: player.clone(),
+        display_name: display_name.clone(),
+        score,
+        columns: columns.clone(),
+        rank,
+        previous_rank,
+    });
+    let detached = pipe.query_async::<redis::aio::Connection, Vec<Option<String>>>(&mut rconn).await
+        .map_err(outcome::redis_cmd_failed)?;
+    delete_replays(&*blobs, detached).await;
+
+    let result = RouteScoreResponse {name: player, display_name, score, columns, rank, periods: None, submitted_at: None, season: None, metadata: None, replay: None};
+
+    Ok((StatusCode::OK, outcome::req_success!(result)))
+}
@@ -0,0 +1,112 @@
+//! Module defining routes for `/player/`.
+
+use std::net::SocketAddr;
+use axum::http::{HeaderMap, StatusCode};
+use axum::extract::{ConnectInfo, Extension, Query};
+use redis::AsyncCommands;
+use serde::Serialize;
+use serde::Deserialize;
+use crate::outcome;
+use crate::shortcuts::audit::{audit_to, Actor, Origin};
+use crate::shortcuts::redis::RedisConnectOr504;
+use crate::shortcuts::token::{get_submission_auth, Authorize, CheckBoardToken, Generate};
+use crate::utils::auth::SubmissionAuth;
+use crate::utils::kebab::Skewer;
+use crate::utils::token::SecureToken;
+use crate::config;
+
+
+/// Expected query params for [`POST /player/`](route_player_post).
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(crate) struct RoutePlayerQuery {
+    /// The name of the board to register the player on.
+    pub(crate) board: String,
+    /// The name of the player to register.
+    pub(crate) player: String,
+}
+
+
+/// Handler for `POST /player/`.
+///
+/// Without an `Authorization` header, registers a new player, refusing to do so if the player is already registered.
+///
+/// With the board token, mints a new token for the player, replacing the previous one if it existed.
+pub(crate) async fn route_player_post(
+    // Redis client
+    Extension(rclient): Extension<redis::Client>,
+    // Client address
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    // Request headers
+    headers: HeaderMap,
+    // Request query
+    Query(RoutePlayerQuery {board, player}): Query<RoutePlayerQuery>,
+) -> outcome::RequestResult {
+    let board = board.to_kebab_lowercase();
+    let display_name = player.clone();
+    let player = player.to_kebab_lowercase_with(&config::PLAYER_NAMES);
+
+    log::trace!("Ensuring the player name follows the name policy...");
+    config::PLAYER_NAME_POLICY.check(&display_name, &player)
+        .map_err(|violation| (StatusCode::UNPROCESSABLE_ENTITY, outcome::req_error!((violation.message()))))?;
+
+    log::trace!("Determining the Redis key names...");
+    let token_key = format!("board:{board}:token");
+    let players_key = format!("board:{board}:players");
+
+    let mut rconn = rclient.get_connection_or_504().await?;
+
+    let (trusted, origin) = match headers.contains_key("Authorization") {
+        true => {
+            let token = headers.get_authorization_or_401("Bearer")?;
+            rconn.check_board_token_or_403(&board, token).await?;
+            (true, Some(Origin::new(Actor::Board, token, addr)))
+        },
+        false => {
+            log::trace!("Ensuring the board exists...");
+            let exists = rconn.exists::<&str, bool>(&token_key).await
+                .map_err(outcome::redis_cmd_failed)?;
+            if !exists {
+                return Err((StatusCode::NOT_FOUND, outcome::req_error!("No such board")))
+            }
+            (false, None)
+        },
+    };
+
+    if let SubmissionAuth::Board = get_submission_auth(&mut rconn, &board).await? {
+        return Err((StatusCode::BAD_REQUEST, outcome::req_error!("Board does not authenticate players")))
+    }
+
+    let token = SecureToken::new_or_500()?;
+    // Players registering themselves are identified by the token they just received.
+    let origin = origin.unwrap_or_else(|| Origin::new(Actor::Player, &token.0, addr));
+
+    let action = match trusted {
+        true => {
+            log::debug!("Minting a new token for {player:?} on {board:?}...");
+            rconn.hset::<&str, &str, &str, ()>(&players_key, &player, &token.0).await
+                .map_err(outcome::redis_cmd_failed)?;
+            "PlayerTokenMinted"
+        },
+        false => {
+            log::debug!("Registering {player:?} on {board:?}...");
+            let registered = rconn.hset_nx::<&str, &str, &str, bool>(&players_key, &player, &token.0).await
+                .map_err(outcome::redis_cmd_failed)?;
+            if !registered {
+                return Err((StatusCode::CONFLICT, outcome::req_error!("Player is already registered")))
+            }
+            "PlayerRegistered"
+        },
+    };
+
+    let mut pipe = redis::pipe();
+    audit_to(&mut pipe, Some(&board), &origin, action, &[
+        ("player", player.clone()),
+    ]);
+    pipe.query_async::<redis::aio::Connection, ()>(&mut rconn).await
+        .map_err(outcome::redis_cmd_failed)?;
+
+    Ok((
+        StatusCode::CREATED,
+        outcome::req_success!((token.0))
+    ))
+}
@@ -0,0 +1,92 @@
+//! Module defining routes for `/score/rank/`.
+
+use axum::http::StatusCode;
+use axum::extract::{Extension, Query};
+use redis::AsyncCommands;
+use serde::Serialize;
+use serde::Deserialize;
+use crate::outcome;
+use crate::shortcuts::redis::RedisConnectOr504;
+use crate::utils::kebab::Skewer;
+use crate::utils::sorting::SortingOrder;
+use crate::config;
+
+
+/// Expected query params for [`GET /score/rank/`](route_score_rank_get).
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(crate) struct RouteScoreRankQuery {
+    /// The board to access.
+    pub board: String,
+    /// The hypothetical score to determine the rank of.
+    pub score: f64,
+    /// The name of the player to compare the score with, if any.
+    pub player: Option<String>,
+}
+
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(crate) struct RouteScoreRankResponse {
+    /// The position the score would have relative to the other scores on the board, zero-based.
+    pub rank: usize,
+    /// Whether the score would improve the current score of the given player, if one was specified.
+    pub improves: Option<bool>,
+}
+
+
+/// Handler for `GET /score/rank/`.
+pub(crate) async fn route_score_rank_get(
+    // Request query
+    Query(RouteScoreRankQuery {board, score, player}): Query<RouteScoreRankQuery>,
+    // Redis client
+    Extension(rclient): Extension<redis::Client>,
+) -> outcome::RequestResult {
+    let board = board.to_kebab_lowercase();
+    let player = player.map(|p| p.to_kebab_lowercase_with(&config::PLAYER_NAMES));
+
+    log::trace!("Ensuring the score is a number...");
+    if !score.is_finite() {
+        return Err((StatusCode::BAD_REQUEST, outcome::req_error!("Score must be a finite number")))
+    }
+
+    log::trace!("Determining the Redis key names...");
+    let order_key = format!("board:{board}:order");
+    let scores_key = format!("board:{board}:scores");
+
+    let mut rconn = rclient.get_connection_or_504().await?;
+
+    log::trace!("Determining sorting order...");
+    let order = rconn.get::<&str, String>(&order_key).await
+        .map_err(outcome::redis_cmd_failed)?;
+    let order = SortingOrder::try_from(order.as_str())
+        .map_err(|_| outcome::redis_unexpected_behaviour())?;
+    log::trace!("Sorting order is: {order:?}");
+
+    log::trace!("Counting the scores better than {score:?}...");
+    let rank = match order {
+        SortingOrder::Ascending => rconn.zcount::<&str, &str, String, usize>(&scores_key, "-inf", format!("({score}")),
+        SortingOrder::Descending => rconn.zcount::<&str, String, &str, usize>(&scores_key, format!("({score}"), "+inf"),
+    }.await.map_err(outcome::redis_cmd_failed)?;
+    log::trace!("Rank would be: {rank:?}");
+
+    let improves = match player {
+        None => None,
+        Some(player) => {
+            log::trace!("Getting the current score of {player}...");
+            let current = rconn.zscore::<&str, &str, Option<f64>>(&scores_key, &player).await
+                .map_err(outcome::redis_cmd_failed)?;
+            log::trace!("Current score is: {current:?}");
+
+            Some(match current {
+                None => true,
+                Some(current) => order.is_improvement(current, score),
+            })
+        }
+    };
+
+    let result = RouteScoreRankResponse {rank, improves};
+
+    Ok((
+        StatusCode::OK,
+        outcome::req_success!(result)
+    ))
+}
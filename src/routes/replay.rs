@@ -0,0 +1,222 @@
+//! Module defining routes for `/score/replay/`.
+
+use std::net::SocketAddr;
+use axum::body::Bytes;
+use axum::http::StatusCode;
+use axum::http::header;
+use axum::http::header::HeaderMap;
+use axum::extract::{ConnectInfo, Extension, Query};
+use axum::response::{IntoResponse, Response};
+use redis::AsyncCommands;
+use serde::Serialize;
+use serde::Deserialize;
+use crate::outcome;
+use crate::routes::score::RouteScoreQuery;
+use crate::shortcuts::audit::{audit_to, Origin};
+use crate::shortcuts::ban::shadow_scores_key;
+use crate::shortcuts::blobs::{blob_store_failed, SharedBlobStore};
+use crate::shortcuts::board::get_score_kind;
+use crate::shortcuts::exact::{exact_scores_key, get_exact_score};
+use crate::shortcuts::redis::RedisConnectOr504;
+use crate::shortcuts::replays::{delete_replays, replays_key, ReplayObject};
+use crate::shortcuts::token::{Authorize, CheckBoardToken, Generate};
+use crate::utils::kebab::Skewer;
+use crate::utils::kind::{ScoreKind, ScoreNumber};
+use crate::utils::token::SecureToken;
+use crate::config;
+
+
+lazy_static::lazy_static! {
+    /// Script attaching a replay to an entry, only if the entry still has the score the replay was uploaded for.
+    ///
+    /// - `KEYS[1]`: the sorted set of the scores containing the entry
+    /// - `KEYS[2]`: the hash of the exact values of the scores
+    /// - `KEYS[3]`: the hash of the replays of the board
+    /// - `ARGV[1]`: the player owning the entry
+    /// - `ARGV[2]`: the score the replay was uploaded for
+    /// - `ARGV[3]`: the exact value of the score, if it is an integer, or an empty string
+    /// - `ARGV[4]`: the [`ReplayObject`] to attach, serialized as JSON
+    ///
+    /// Returns whether the replay was attached, and the replay it replaced, if any.
+    static ref ATTACH_REPLAY_SCRIPT: redis::Script = redis::Script::new(r#"
+        local current = redis.call("ZSCORE", KEYS[1], ARGV[1])
+        if not current or tonumber(current) ~= tonumber(ARGV[2]) then
+            return {0, false}
+        end
+        if ARGV[3] ~= "" and redis.call("HGET", KEYS[2], ARGV[1]) ~= ARGV[3] then
+            return {0, false}
+        end
+        local previous = redis.call("HGET", KEYS[3], ARGV[1])
+        redis.call("HSET", KEYS[3], ARGV[1], ARGV[4])
+        return {1, previous}
+    "#);
+}
+
+
+/// Expected query params for [`PUT /score/replay/`](route_score_replay_put).
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(crate) struct RouteScoreReplayPutQuery {
+    /// The board to access.
+    pub board: String,
+    /// The name of the player to attach the replay to the entry of.
+    pub player: String,
+    /// The score of the entry the replay proves, as returned by [`PUT /score/`](crate::routes::score::route_score_put).
+    ///
+    /// Kept as a string, so that the scores of [`ScoreKind::Integer`] boards can be compared exactly.
+    pub score: String,
+}
+
+
+/// Handler for `GET /score/replay/`.
+///
+/// Responds with the replay itself, rather than with JSON.
+pub(crate) async fn route_score_replay_get(
+    // Request query
+    Query(RouteScoreQuery {board, player, season}): Query<RouteScoreQuery>,
+    // Redis client
+    Extension(rclient): Extension<redis::Client>,
+    // Blob store
+    Extension(blobs): Extension<SharedBlobStore>,
+) -> Result<Response, outcome::RequestTuple> {
+    let board = board.to_kebab_lowercase();
+    let player = player.to_kebab_lowercase_with(&config::PLAYER_NAMES);
+    let season = season.map(|season| season.to_kebab_lowercase());
+
+    let mut rconn = rclient.get_connection_or_504().await?;
+
+    log::trace!("Getting the replay of {player:?}...");
+    let replay = rconn.hget::<String, &str, Option<String>>(replays_key(&board, season.as_deref()), &player).await
+        .map_err(outcome::redis_cmd_failed)?
+        .ok_or_else(|| (StatusCode::NOT_FOUND, outcome::req_error!("Player has no replay on this board")))?;
+    let replay = serde_json::from_str::<ReplayObject>(&replay)
+        .map_err(|_| outcome::redis_unexpected_behaviour())?;
+    log::trace!("Replay is: {replay:?}");
+
+    let data = blobs.get(&replay.id).await
+        .map_err(blob_store_failed)?
+        .ok_or_else(|| (StatusCode::NOT_FOUND, outcome::req_error!("Player has no replay on this board")))?;
+
+    Ok((
+        StatusCode::OK,
+        [
+            (header::CONTENT_TYPE, "application/octet-stream".to_string()),
+            (header::CONTENT_DISPOSITION, format!("attachment; filename=\"{board}-{player}.replay\"")),
+        ],
+        data,
+    ).into_response())
+}
+
+
+/// Handler for `PUT /score/replay/`.
+///
+/// The replay is attached to the current entry of the player, replacing any previous one, and is discarded along with it.
+pub(crate) async fn route_score_replay_put(
+    // Redis client
+    Extension(rclient): Extension<redis::Client>,
+    // Blob store
+    Extension(blobs): Extension<SharedBlobStore>,
+    // Client address
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    // Request headers
+    headers: HeaderMap,
+    // Request query
+    Query(RouteScoreReplayPutQuery {board, player, score}): Query<RouteScoreReplayPutQuery>,
+    // Request body
+    body: Bytes,
+) -> outcome::RequestResult {
+    let board = board.to_kebab_lowercase();
+    let player = player.to_kebab_lowercase_with(&config::PLAYER_NAMES);
+
+    if body.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, outcome::req_error!("Replay cannot be empty")))
+    }
+
+    log::trace!("Determining the Redis key names...");
+    let mut scores_key = format!("board:{board}:scores");
+    let replays_key = replays_key(&board, None);
+
+    let token = headers.get_authorization_or_401("Bearer")?;
+    let mut rconn = rclient.get_connection_or_504().await?;
+
+    let actor = rconn.check_board_or_player_token_or_403(&board, &player, token).await?;
+    let origin = Origin::new(actor, token, addr);
+
+    let kind = get_score_kind(&mut rconn, &board).await?;
+
+    log::trace!("Parsing the score of the entry...");
+    let score = match kind {
+        ScoreKind::Integer => score.parse::<i64>().ok().map(ScoreNumber::Integer),
+        ScoreKind::Float => score.parse::<f64>().ok().map(ScoreNumber::Float),
+    };
+    let score = score
+        .ok_or_else(|| (StatusCode::BAD_REQUEST, outcome::req_error!("Score is not a valid number for this board")))?;
+
+    log::trace!("Getting the current score...");
+    let mut current = rconn.zscore::<&str, &str, Option<f64>>(&scores_key, &player).await
+        .map_err(outcome::redis_cmd_failed)?;
+    if current.is_none() {
+        log::trace!("Checking for a hidden score...");
+        let shadow_key = shadow_scores_key(&board);
+        current = rconn.zscore::<&str, &str, Option<f64>>(&shadow_key, &player).await
+            .map_err(outcome::redis_cmd_failed)?;
+        scores_key = shadow_key;
+    }
+    let current = current
+        .ok_or_else(|| (StatusCode::NOT_FOUND, outcome::req_error!("Player has no score on this board")))?;
+    let current = get_exact_score(&mut rconn, &scores_key, kind, &player, current).await
+        .map_err(outcome::redis_cmd_failed)?;
+    log::trace!("Score is: {current:?}");
+
+    if current != score {
+        return Err((StatusCode::CONFLICT, outcome::req_error!("Replay does not match the current score of the player")))
+    }
+
+    let replay = ReplayObject {
+        id: SecureToken::new_or_500()?.0,
+        score,
+        size: body.len(),
+        uploaded_at: chrono::Utc::now().timestamp(),
+    };
+
+    log::debug!("Storing replay {:?} of {player:?} on {board:?}...", replay.id);
+    blobs.put(&replay.id, body).await
+        .map_err(blob_store_failed)?;
+
+    log::trace!("Attaching the replay to the entry...");
+    let (attached, previous) = ATTACH_REPLAY_SCRIPT
+        .key(&scores_key)
+        .key(exact_scores_key(&scores_key))
+        .key(&replays_key)
+        .arg(&player)
+        .arg(score.as_f64())
+        .arg(match score {
+            ScoreNumber::Integer(exact) => exact.to_string(),
+            ScoreNumber::Float(_) => String::new(),
+        })
+        .arg(serde_json::to_string(&replay).expect("replay to be serializable"))
+        .invoke_async::<redis::aio::Connection, (bool, Option<String>)>(&mut rconn).await
+        .map_err(outcome::redis_cmd_failed)?;
+
+    if !attached {
+        log::debug!("Score of {player:?} changed during the upload, discarding the replay...");
+        if let Err(err) = blobs.delete(&replay.id).await {
+            log::error!("{err:#?}");
+        }
+        return Err((StatusCode::CONFLICT, outcome::req_error!("Replay does not match the current score of the player")))
+    }
+
+    delete_replays(&*blobs, vec![previous]).await;
+
+    let mut pipe = redis::pipe();
+    audit_to(&mut pipe, Some(&board), &origin, "ReplayUploaded", &[
+        ("player", player.clone()),
+        ("size", replay.size.to_string()),
+    ]);
+    pipe.query_async::<redis::aio::Connection, ()>(&mut rconn).await
+        .map_err(outcome::redis_cmd_failed)?;
+
+    Ok((
+        StatusCode::CREATED,
+        outcome::req_success!(replay)
+    ))
+}
@@ -1,16 +1,32 @@
 //! Module defining routes for `/score/`.
 
+use std::net::SocketAddr;
 use axum::http::StatusCode;
 use axum::http::header::HeaderMap;
-use axum::extract::{Extension, Json, Query};
+use axum::extract::{ConnectInfo, Extension, Json, Query};
 use redis::AsyncCommands;
 use serde::Serialize;
 use serde::Deserialize;
+use serde_json::{Map, Value};
 use crate::outcome;
+use crate::shortcuts::audit::Origin;
+use crate::shortcuts::ban::{get_ban_kind, get_shadow_rank, shadow_scores_key, BanKind};
+use crate::shortcuts::blobs::{BlobStore, SharedBlobStore};
+use crate::shortcuts::board::{get_score_kind, get_score_schema};
+use crate::shortcuts::exact::{get_exact_score, get_rank};
+use crate::shortcuts::idempotency::IdempotencyKey;
+use crate::shortcuts::names::get_display_names;
 use crate::shortcuts::redis::RedisConnectOr504;
-use crate::shortcuts::token::Authorize;
+use crate::shortcuts::replays::{metadata_key, replays_key, ReplayObject};
+use crate::shortcuts::season::archived_season_scores_key;
+use crate::shortcuts::submit::{get_submission_target, prepare_submission, write_submissions};
+use crate::shortcuts::token::{Authorize, CheckBoardToken};
 use crate::utils::kebab::Skewer;
+use crate::utils::kind::ScoreNumber;
+use crate::utils::period::Period;
+use crate::utils::schema::ScoreValue;
 use crate::utils::sorting::SortingOrder;
+use crate::config;
 
 
 /// Query parameters for `/score/` routes.
@@ -20,36 +36,127 @@ pub(crate) struct RouteScoreQuery {
     pub board: String,
     /// The name of the player to access the score of.
     pub player: String,
+    /// The archived season to access, instead of the live board.
+    pub season: Option<String>,
+}
+
+
+/// Expected body for [`PUT /score/`](route_score_put).
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+pub(crate) enum RouteScorePutBody {
+    /// A score, along with metadata about the run it was achieved in.
+    Detailed {
+        /// The score to submit.
+        score: ScoreValue,
+        /// Information about the run, such as the character, level or seed played, or the platform and build of the game.
+        #[serde(default)]
+        metadata: Option<Map<String, Value>>,
+    },
+    /// A bare score.
+    Bare(ScoreValue),
+}
+
+impl RouteScorePutBody {
+    /// Split the body into the score and its serialized metadata, ensuring that the metadata is within limits.
+    fn into_parts_or_422(self) -> Result<(ScoreValue, Option<String>), outcome::RequestTuple> {
+        let (score, metadata) = match self {
+            Self::Detailed {score, metadata} => (score, metadata),
+            Self::Bare(score) => (score, None),
+        };
+
+        let metadata = metadata.map(|metadata| serde_json::to_string(&metadata).expect("metadata to be serializable"));
+
+        log::trace!("Ensuring the metadata is within limits...");
+        if matches!(&metadata, Some(metadata) if metadata.len() > *config::SCORES_METADATA_MAX_BYTES) {
+            return Err((StatusCode::UNPROCESSABLE_ENTITY, outcome::req_error!("Score metadata is too large")))
+        }
+
+        Ok((score, metadata))
+    }
 }
 
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub(crate) struct RouteScoreResponse {
+    /// The normalized name of the user.
+    pub name: String,
+    /// The name of the user, as it was originally submitted.
+    pub display_name: String,
     /// The score the user has on the board.
-    pub score: f64,
+    pub score: ScoreNumber,
+    /// The values of the columns of the score, if the board has multiple.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub columns: Option<Vec<f64>>,
     /// The position of the user relative to the other users on the board, zero-based.
     pub rank: usize,
+    /// The position of the user on each period leaderboard the score was submitted to, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub periods: Option<Vec<PeriodRankObject>>,
+    /// When the score was submitted, as a Unix timestamp, if it was submitted as a separate entry to a [`BoardMode::Initials`] board.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub submitted_at: Option<i64>,
+    /// The archived season the score was submitted to, if it was synchronized after the end of the season it was achieved in.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub season: Option<String>,
+    /// The metadata of the run the score was achieved in, if it was submitted with any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<Value>,
+    /// The replay or proof attached to the score, if one was uploaded.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub replay: Option<ReplayObject>,
+}
+
+
+/// The rank of a submitted score on one of the period leaderboards it belongs to.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(crate) struct PeriodRankObject {
+    /// The tracked period.
+    pub period: Period,
+    /// The identifier of the period the score was achieved in, such as `2022-W44`.
+    pub id: String,
+    /// The position of the user relative to the other users on the period leaderboard, zero-based.
+    pub rank: usize,
 }
 
 
 /// Handler for `GET /score/`.
 pub(crate) async fn route_score_get(
     // Request query
-    Query(RouteScoreQuery {board, player}): Query<RouteScoreQuery>,
+    Query(RouteScoreQuery {board, player, season}): Query<RouteScoreQuery>,
     // Redis client
     Extension(rclient): Extension<redis::Client>,
 ) -> outcome::RequestResult {
     let board = board.to_kebab_lowercase();
-    let player = player.to_kebab_lowercase();
+    let player = player.to_kebab_lowercase_with(&config::PLAYER_NAMES);
 
     log::trace!("Determining the Redis key names...");
     let order_key = format!("board:{board}:order");
-    let scores_key = format!("board:{board}:scores");
+    let mut scores_key = format!("board:{board}:scores");
+    let mut shadow = false;
 
     let mut rconn = rclient.get_connection_or_504().await?;
 
+    let season = season.map(|season| season.to_kebab_lowercase());
+
+    if let Some(season) = &season {
+        scores_key = archived_season_scores_key(&mut rconn, &board, season).await?;
+        log::trace!("Using the scores of the archived season: {scores_key:?}");
+    }
+    else if let Some(BanKind::ShadowBan) = get_ban_kind(&mut rconn, &board, &player).await? {
+        log::trace!("Player is shadow-banned, checking for a hidden score...");
+        let shadow_key = shadow_scores_key(&board);
+        let hidden = rconn.zscore::<&str, &str, Option<f64>>(&shadow_key, &player).await
+            .map_err(outcome::redis_cmd_failed)?;
+        if hidden.is_some() {
+            log::trace!("Using the hidden scores: {shadow_key:?}");
+            scores_key = shadow_key;
+            shadow = true;
+        }
+    }
+
     log::trace!("Getting score...");
-    let score = rconn.zscore(&scores_key, &player).await
+    let score = rconn.zscore::<&str, &str, f64>(&scores_key, &player).await
         .map_err(outcome::redis_cmd_failed)?;
     log::trace!("Score is: {score:?}");
 
@@ -60,14 +167,44 @@ pub(crate) async fn route_score_get(
         .map_err(|_| outcome::redis_unexpected_behaviour())?;
     log::trace!("Sorting order is: {order:?}");
 
-    log::trace!("Getting rank...");
-    let rank = match order {
-        SortingOrder::Ascending => rconn.zrank::<&str, &str, usize>(&scores_key, &player),
-        SortingOrder::Descending => rconn.zrevrank::<&str, &str, usize>(&scores_key, &player),
-    }.await.map_err(outcome::redis_cmd_failed)?;
+    let kind = get_score_kind(&mut rconn, &board).await?;
+    let schema = get_score_schema(&mut rconn, &board).await?;
+
+    log::trace!("Getting display name...");
+    let display_name = get_display_names(&mut rconn, &board, season.as_deref(), &[&player]).await
+        .map_err(outcome::redis_cmd_failed)?
+        .pop()
+        .ok_or_else(outcome::redis_unexpected_behaviour)?;
+
+    let columns = schema.map(|schema| schema.decode(score, order));
+    let score = get_exact_score(&mut rconn, &scores_key, kind, &player, score).await
+        .map_err(outcome::redis_cmd_failed)?;
+
+    let rank = match shadow {
+        true => get_shadow_rank(&mut rconn, &board, kind, order, &player, score).await
+            .map_err(outcome::redis_cmd_failed)?,
+        false => get_rank(&mut rconn, &scores_key, kind, order, &player).await
+            .map_err(outcome::redis_cmd_failed)?
+            .ok_or_else(outcome::redis_unexpected_behaviour)?,
+    };
     log::trace!("Rank is: {rank:?}");
 
-    let result = RouteScoreResponse {score, rank};
+    log::trace!("Getting metadata and replay...");
+    let (metadata, replay) = redis::pipe()
+        .hget(metadata_key(&board, season.as_deref()), &player)
+        .hget(replays_key(&board, season.as_deref()), &player)
+        .query_async::<redis::aio::Connection, (Option<String>, Option<String>)>(&mut rconn).await
+        .map_err(outcome::redis_cmd_failed)?;
+    let metadata = metadata
+        .map(|metadata| serde_json::from_str::<Value>(&metadata))
+        .transpose()
+        .map_err(|_| outcome::redis_unexpected_behaviour())?;
+    let replay = replay
+        .map(|replay| serde_json::from_str::<ReplayObject>(&replay))
+        .transpose()
+        .map_err(|_| outcome::redis_unexpected_behaviour())?;
+
+    let result = RouteScoreResponse {name: player, display_name, score, columns, rank, periods: None, submitted_at: None, season: None, metadata, replay};
 
     Ok((
         StatusCode::OK,
@@ -76,73 +213,93 @@ pub(crate) async fn route_score_get(
 }
 
 
+/// Submit the score in `body` on behalf of `player` to `board`, once the submitter has been authorized.
+///
+/// Fails with the outer [`Result`] if the score was refused before anything was written, and with the inner one if writing it failed.
+async fn submit_score(rconn: &mut redis::aio::Connection, blobs: &dyn BlobStore, board: &str, player: String, display_name: String, body: RouteScorePutBody, origin: Origin) -> Result<outcome::RequestResult, outcome::RequestTuple> {
+    let (score, metadata) = body.into_parts_or_422()?;
+
+    let target = get_submission_target(rconn, board).await?;
+    let mut submission = prepare_submission(rconn, &target, player, display_name, &score, origin).await?;
+    submission.metadata = metadata;
+
+    let written = write_submissions(rconn, blobs, &[(&target, &submission)]).await
+        .map_err(outcome::redis_cmd_failed)
+        .and_then(|mut written| written.pop().ok_or_else(outcome::redis_unexpected_behaviour));
+    let written = match written {
+        Ok(written) => written,
+        Err(err) => return Ok(Err(err)),
+    };
+    log::trace!("Score is now {:?}, ranked {:?}", written.score, written.rank);
+
+    let changed = written.changed;
+    let result = submission.into_response(&target, written);
+
+    Ok(Ok((
+        match changed {
+            true => StatusCode::CREATED,
+            false => StatusCode::OK,
+        },
+        outcome::req_success!(result)
+    )))
+}
+
+
 /// Handler for `PUT /score/`.
+///
+/// If the request has an `Idempotency-Key` header, the response is stored, and replayed for retries of the same request.
 pub(crate) async fn route_score_put(
     // Redis client (MUST BE ON TOP SINCE AXUM 0.6?)
     Extension(rclient): Extension<redis::Client>,
+    // Blob store
+    Extension(blobs): Extension<SharedBlobStore>,
+    // Client address
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
     // Request headers
     headers: HeaderMap,
     // Request query
-    Query(RouteScoreQuery {board, player}): Query<RouteScoreQuery>,
+    Query(RouteScoreQuery {board, player, season}): Query<RouteScoreQuery>,
     // Request body
-    Json(score): Json<f64>,
+    Json(body): Json<RouteScorePutBody>,
 ) -> outcome::RequestResult {
     let board = board.to_kebab_lowercase();
-    let player = player.to_kebab_lowercase();
+    let display_name = player.clone();
+    let player = player.to_kebab_lowercase_with(&config::PLAYER_NAMES);
 
-    log::trace!("Determining the Redis key names...");
-    let order_key = format!("board:{board}:order");
-    let token_key = format!("board:{board}:token");
-    let scores_key = format!("board:{board}:scores");
+    if season.is_some() {
+        return Err((StatusCode::BAD_REQUEST, outcome::req_error!("Cannot submit scores to an archived season")))
+    }
+
+    log::trace!("Ensuring the player name follows the name policy...");
+    config::PLAYER_NAME_POLICY.check(&display_name, &player)
+        .map_err(|violation| (StatusCode::UNPROCESSABLE_ENTITY, outcome::req_error!((violation.message()))))?;
 
     let token = headers.get_authorization_or_401("Bearer")?;
+    let idempotency = IdempotencyKey::from_headers_or_400(&headers, &board, token, &(&display_name, &body))?;
     let mut rconn = rclient.get_connection_or_504().await?;
 
-    log::trace!("Checking if the token exists and matches...");
-    let btoken = rconn.get::<&str, String>(&token_key).await
-        .map_err(outcome::redis_cmd_failed)?;
-
-    if btoken.is_empty() {
-        log::trace!("Token is not set, board does not exist...");
-        return Err((StatusCode::NOT_FOUND, outcome::req_error!("No such board")))
-    }
+    let actor = rconn.check_board_or_player_token_or_403(&board, &player, token).await?;
+    let origin = Origin::new(actor, token, addr);
 
-    if btoken != token {
-        log::trace!("Token does not match, forbidding...");
-        return Err((StatusCode::FORBIDDEN, outcome::req_error!("Invalid board token"))) 
-    }
-    
-    log::trace!("Determining sorting order...");
-    let order = rconn.get::<&str, String>(&order_key).await
-        .map_err(outcome::redis_cmd_failed)?;
-    let order = SortingOrder::try_from(order.as_str())
-        .map_err(|_| outcome::redis_unexpected_behaviour())?;
-    log::trace!("Sorting order is: {order:?}");
-
-    log::trace!("Inserting score: {score:?}");
-    let changed = redis::cmd("ZADD").arg(&scores_key).arg(order.zadd_mode()).arg("CH").arg(&score).arg(&player)
-        .query_async::<redis::aio::Connection, i32>(&mut rconn).await
-        .map_err(outcome::redis_cmd_failed)?;
-
-    log::trace!("Getting the new score...");
-    let nscore = rconn.zscore::<&str, &str, f64>(&scores_key, &player).await
-        .map_err(outcome::redis_cmd_failed)?;
-    log::trace!("Received score: {nscore:?}");
+    let idempotency = match idempotency {
+        Some(idempotency) => match idempotency.reserve_or_replay(&mut rconn).await? {
+            Some(replay) => return Ok(replay),
+            None => Some(idempotency),
+        },
+        None => None,
+    };
 
-    log::trace!("Getting rank...");
-    let rank = match order {
-        SortingOrder::Ascending => rconn.zrank::<&str, &str, usize>(&scores_key, &player),
-        SortingOrder::Descending => rconn.zrevrank::<&str, &str, usize>(&scores_key, &player),
-    }.await.map_err(outcome::redis_cmd_failed)?;
-    log::trace!("Rank is: {rank:?}");
+    let result = submit_score(&mut rconn, &*blobs, &board, player, display_name, body, origin).await;
 
-    let result = RouteScoreResponse {score, rank};
+    if let Some(idempotency) = idempotency {
+        let stored = match &result {
+            Ok(result) => idempotency.complete(&mut rconn, result).await,
+            Err(_) => idempotency.release(&mut rconn).await,
+        };
+        if let Err(err) = stored {
+            log::error!("{err:#?}");
+        }
+    }
 
-    Ok((
-        match changed.gt(&0) {
-            true => StatusCode::CREATED,
-            false => StatusCode::OK,
-        },
-        outcome::req_success!(result)
-    ))
-}
\ No newline at end of file
+    result?
+}
@@ -0,0 +1,137 @@
+//! Module defining routes for `/board/season/`.
+
+use std::net::SocketAddr;
+use axum::http::{HeaderMap, StatusCode};
+use axum::extract::{ConnectInfo, Extension, Json, Query};
+use redis::AsyncCommands;
+use serde::Serialize;
+use serde::Deserialize;
+use crate::outcome;
+use crate::shortcuts::audit::{audit_to, Actor, Origin};
+use crate::shortcuts::redis::RedisConnectOr504;
+use crate::shortcuts::season::{archive_season, SCHEDULED_SEASONS_KEY};
+use crate::shortcuts::token::{Authorize, CheckBoardToken};
+use crate::utils::kebab::Skewer;
+
+
+/// Expected query params for `/board/season/` routes.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(crate) struct RouteBoardSeasonQuery {
+    /// The name of the board to access.
+    pub(crate) board: String,
+}
+
+
+/// Expected body for [`POST /board/season/`](route_board_season_post).
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(crate) struct RouteBoardSeasonBody {
+    /// The name of the season to archive the current scores as.
+    pub(crate) name: String,
+    /// The UNIX timestamp at which the season should be archived, or [`None`] to archive it immediately.
+    pub(crate) at: Option<i64>,
+}
+
+
+/// An archived season, as a serializable struct.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(crate) struct SeasonObject {
+    /// The name of the season.
+    pub(crate) name: String,
+    /// The UNIX timestamp at which the season was archived.
+    pub(crate) archived_at: i64,
+}
+
+impl From<(String, i64)> for SeasonObject {
+    fn from(t: (String, i64)) -> Self {
+        SeasonObject {
+            name: t.0,
+            archived_at: t.1,
+        }
+    }
+}
+
+
+/// Handler for `GET /board/season/`.
+pub(crate) async fn route_board_season_get(
+    // Request query
+    Query(RouteBoardSeasonQuery {board}): Query<RouteBoardSeasonQuery>,
+    // Redis client
+    Extension(rclient): Extension<redis::Client>,
+) -> outcome::RequestResult {
+
+    let board = board.to_kebab_lowercase();
+
+    log::trace!("Determining the Redis key names...");
+    let seasons_key = format!("board:{board}:seasons");
+
+    let mut rconn = rclient.get_connection_or_504().await?;
+
+    log::trace!("Retrieving the archived seasons of {board}...");
+    let result: Vec<SeasonObject> = rconn.zrange_withscores::<&str, Vec<(String, i64)>>(&seasons_key, 0, -1).await
+        .map_err(outcome::redis_cmd_failed)?
+        .into_iter()
+        .map(From::<(String, i64)>::from)
+        .collect();
+
+    Ok((StatusCode::OK, outcome::req_success!(result)))
+}
+
+
+/// Handler for `POST /board/season/`.
+pub(crate) async fn route_board_season_post(
+    // Redis client
+    Extension(rclient): Extension<redis::Client>,
+    // Client address
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    // Request headers
+    headers: HeaderMap,
+    // Request query
+    Query(RouteBoardSeasonQuery {board}): Query<RouteBoardSeasonQuery>,
+    // Request body
+    Json(RouteBoardSeasonBody {name, at}): Json<RouteBoardSeasonBody>,
+) -> outcome::RequestResult {
+
+    let board = board.to_kebab_lowercase();
+    let name = name.to_kebab_lowercase();
+
+    let token = headers.get_authorization_or_401("Bearer")?;
+    let mut rconn = rclient.get_connection_or_504().await?;
+
+    rconn.check_board_token_or_403(&board, token).await?;
+    let origin = Origin::new(Actor::Board, token, addr);
+
+    match at {
+        Some(at) if at > chrono::Utc::now().timestamp() => {
+            log::debug!("Scheduling archival of season {name:?} of {board:?} at {at}...");
+            let scheduled = serde_json::to_string(&(&board, &name))
+                .expect("scheduled season to be serializable");
+            let mut pipe = redis::pipe();
+            pipe.zadd(SCHEDULED_SEASONS_KEY, scheduled, at).ignore();
+            audit_to(&mut pipe, Some(&board), &origin, "SeasonScheduled", &[
+                ("season", name.clone()),
+                ("at", at.to_string()),
+            ]);
+            pipe.query_async::<redis::aio::Connection, ()>(&mut rconn).await
+                .map_err(outcome::redis_cmd_failed)?;
+
+            Ok((StatusCode::ACCEPTED, outcome::req_success!(null)))
+        },
+        _ => {
+            let archived = archive_season(&mut rconn, &board, &name).await
+                .map_err(outcome::redis_cmd_failed)?;
+
+            if !archived {
+                return Err((StatusCode::CONFLICT, outcome::req_error!("Season already exists")))
+            }
+
+            let mut pipe = redis::pipe();
+            audit_to(&mut pipe, Some(&board), &origin, "SeasonArchived", &[
+                ("season", name.clone()),
+            ]);
+            pipe.query_async::<redis::aio::Connection, ()>(&mut rconn).await
+                .map_err(outcome::redis_cmd_failed)?;
+
+            Ok((StatusCode::CREATED, outcome::req_success!(null)))
+        },
+    }
+}
@@ -0,0 +1,96 @@
+//! Module defining routes for `/board/state/`.
+
+use std::net::SocketAddr;
+use axum::http::{HeaderMap, StatusCode};
+use axum::extract::{ConnectInfo, Extension, Json, Query};
+use redis::AsyncCommands;
+use serde::Serialize;
+use serde::Deserialize;
+use crate::outcome;
+use crate::shortcuts::audit::{audit_to, Actor, Origin};
+use crate::shortcuts::redis::RedisConnectOr504;
+use crate::shortcuts::state::{get_board_state, BoardState};
+use crate::shortcuts::token::{Authorize, CheckBoardToken};
+use crate::utils::kebab::Skewer;
+
+
+/// Expected query params for `/board/state/` routes.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(crate) struct RouteBoardStateQuery {
+    /// The name of the board to access.
+    pub(crate) board: String,
+}
+
+
+/// Handler for `GET /board/state/`.
+pub(crate) async fn route_board_state_get(
+    // Request query
+    Query(RouteBoardStateQuery {board}): Query<RouteBoardStateQuery>,
+    // Redis client
+    Extension(rclient): Extension<redis::Client>,
+) -> outcome::RequestResult {
+
+    let board = board.to_kebab_lowercase();
+
+    log::trace!("Determining the Redis key names...");
+    let order_key = format!("board:{board}:order");
+
+    let mut rconn = rclient.get_connection_or_504().await?;
+
+    log::trace!("Ensuring the board exists...");
+    let exists = rconn.exists::<&str, bool>(&order_key).await
+        .map_err(outcome::redis_cmd_failed)?;
+    if !exists {
+        return Err((StatusCode::NOT_FOUND, outcome::req_error!("No such board")))
+    }
+
+    let result = get_board_state(&mut rconn, &board).await?;
+
+    Ok((StatusCode::OK, outcome::req_success!(result)))
+}
+
+
+/// Handler for `PUT /board/state/`.
+pub(crate) async fn route_board_state_put(
+    // Redis client
+    Extension(rclient): Extension<redis::Client>,
+    // Client address
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    // Request headers
+    headers: HeaderMap,
+    // Request query
+    Query(RouteBoardStateQuery {board}): Query<RouteBoardStateQuery>,
+    // Request body
+    Json(state): Json<BoardState>,
+) -> outcome::RequestResult {
+
+    let board = board.to_kebab_lowercase();
+
+    log::trace!("Determining the Redis key names...");
+    let state_key = format!("board:{board}:state");
+
+    let token = headers.get_authorization_or_401("Bearer")?;
+    let mut rconn = rclient.get_connection_or_504().await?;
+
+    rconn.check_board_token_or_403(&board, token).await?;
+    let origin = Origin::new(Actor::Board, token, addr);
+
+    log::debug!("Setting the state of {board:?} to {state:?}");
+    let mut pipe = redis::pipe();
+    pipe.atomic();
+    pipe.del(&state_key).ignore();
+    pipe.hset(&state_key, "locked", state.locked).ignore();
+    if let Some(opens_at) = state.opens_at {
+        pipe.hset(&state_key, "opens_at", opens_at).ignore();
+    }
+    if let Some(closes_at) = state.closes_at {
+        pipe.hset(&state_key, "closes_at", closes_at).ignore();
+    }
+    audit_to(&mut pipe, Some(&board), &origin, "StateChanged", &[
+        ("state", serde_json::to_string(&state).expect("state to be serializable")),
+    ]);
+    pipe.query_async::<redis::aio::Connection, ()>(&mut rconn).await
+        .map_err(outcome::redis_cmd_failed)?;
+
+    Ok((StatusCode::OK, outcome::req_success!(state)))
+}
@@ -0,0 +1,270 @@
+//! Module defining routes for `/board/stats/`.
+
+use axum::http::StatusCode;
+use axum::extract::{Extension, Query};
+use redis::AsyncCommands;
+use serde::Serialize;
+use serde::Deserialize;
+use crate::outcome;
+use crate::shortcuts::exact::sum_scores_key;
+use crate::shortcuts::redis::RedisConnectOr504;
+use crate::utils::kebab::Skewer;
+use crate::utils::sorting::SortingOrder;
+use crate::config;
+
+
+/// How many seconds a rebuilt running sum of the scores is kept up to date for, before being rebuilt again from scratch.
+///
+/// Each update of the sum rounds it, so it is rebuilt periodically to keep the rounding errors from accumulating.
+const SUM_REBUILD_SECONDS: usize = 3600;
+
+
+lazy_static::lazy_static! {
+    /// Script getting the number of scores of a board and their running sum, rebuilding the sum if it does not exist.
+    ///
+    /// - `KEYS[1]`: the scores key of the board
+    /// - `KEYS[2]`: the key of the running sum of the scores
+    /// - `ARGV[1]`: how many seconds the rebuilt sum should expire after
+    ///
+    /// Returns the number of scores, followed by their sum as a string.
+    static ref SUM_SCRIPT: redis::Script = redis::Script::new(r#"
+        local count = redis.call("ZCARD", KEYS[1])
+        local sum = redis.call("GET", KEYS[2])
+        if not sum then
+            local total = 0
+            for start = 0, count - 1, 1000 do
+                local page = redis.call("ZRANGE", KEYS[1], start, start + 999, "WITHSCORES")
+                for i = 2, #page, 2 do
+                    total = total + tonumber(page[i])
+                end
+            end
+            sum = string.format("%.17g", total)
+            redis.call("SET", KEYS[2], sum, "EX", ARGV[1])
+        end
+        return {count, sum}
+    "#);
+}
+
+
+/// Expected query params for [`GET /board/stats/`](route_board_stats_get).
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(crate) struct RouteBoardStatsQuery {
+    /// The name of the board to compute the statistics of.
+    pub(crate) board: String,
+    /// Comma-separated list of the percentiles to compute, between `0` and `100`.
+    pub(crate) percentiles: Option<String>,
+    /// How many buckets the histogram should have.
+    pub(crate) buckets: Option<usize>,
+}
+
+
+/// The score at a certain percentile of a board.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(crate) struct PercentileObject {
+    /// The requested percentile.
+    pub(crate) percentile: f64,
+    /// The worst score ranked at or above the given percentage of the scores, counting from the best one according to the [`SortingOrder`] of the board.
+    pub(crate) score: f64,
+}
+
+
+/// A bucket of the histogram of a board.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(crate) struct BucketObject {
+    /// The lowest score included in the bucket.
+    pub(crate) min: f64,
+    /// The highest score of the bucket; only the last bucket includes it.
+    pub(crate) max: f64,
+    /// How many scores fall in the bucket.
+    pub(crate) count: usize,
+}
+
+
+/// Statistics about the scores of a board.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(crate) struct RouteBoardStatsResponse {
+    /// How many players have a score on the board.
+    pub(crate) count: usize,
+    /// The lowest score on the board.
+    pub(crate) min: Option<f64>,
+    /// The highest score on the board.
+    pub(crate) max: Option<f64>,
+    /// The arithmetic mean of the scores on the board.
+    pub(crate) mean: Option<f64>,
+    /// The median of the scores on the board.
+    pub(crate) median: Option<f64>,
+    /// The requested percentiles.
+    pub(crate) percentiles: Vec<PercentileObject>,
+    /// The histogram of the scores, with buckets of equal width between [`min`](Self::min) and [`max`](Self::max).
+    pub(crate) histogram: Vec<BucketObject>,
+}
+
+
+/// Get the score at the given zero-based index of the board, in ascending order.
+async fn score_at(rconn: &mut redis::aio::Connection, scores_key: &str, index: usize) -> Result<f64, outcome::RequestTuple> {
+    log::trace!("Getting the score at index {index}...");
+    rconn.zrange_withscores::<&str, Vec<(String, f64)>>(scores_key, index as isize, index as isize).await
+        .map_err(outcome::redis_cmd_failed)?
+        .first()
+        .map(|(_, score)| *score)
+        .ok_or_else(outcome::redis_unexpected_behaviour)
+}
+
+/// Get the score at the given zero-based rank of a board with `count` scores, sorted by `order`.
+async fn score_at_rank(rconn: &mut redis::aio::Connection, scores_key: &str, order: SortingOrder, count: usize, rank: usize) -> Result<f64, outcome::RequestTuple> {
+    match order {
+        SortingOrder::Ascending => score_at(rconn, scores_key, rank).await,
+        SortingOrder::Descending => score_at(rconn, scores_key, count - 1 - rank).await,
+    }
+}
+
+/// Compute the statistics of a board from scratch, except for the mean, which is computed from the running sum of the scores.
+async fn compute_stats(rconn: &mut redis::aio::Connection, scores_key: &str, order: SortingOrder, percentiles: &[f64], buckets: usize) -> Result<RouteBoardStatsResponse, outcome::RequestTuple> {
+    log::trace!("Counting and summing scores...");
+    let (count, sum) = SUM_SCRIPT
+        .key(scores_key)
+        .key(sum_scores_key(scores_key))
+        .arg(SUM_REBUILD_SECONDS)
+        .invoke_async::<redis::aio::Connection, (usize, f64)>(rconn).await
+        .map_err(outcome::redis_cmd_failed)?;
+
+    if count == 0 {
+        log::trace!("Board is empty, there are no statistics to compute.");
+        return Ok(RouteBoardStatsResponse {
+            count,
+            min: None,
+            max: None,
+            mean: None,
+            median: None,
+            percentiles: vec![],
+            histogram: vec![],
+        })
+    }
+
+    let min = score_at(rconn, scores_key, 0).await?;
+    let max = score_at(rconn, scores_key, count - 1).await?;
+
+    log::trace!("Computing median...");
+    let median = match count % 2 {
+        1 => score_at(rconn, scores_key, count / 2).await?,
+        _ => (score_at(rconn, scores_key, count / 2 - 1).await? + score_at(rconn, scores_key, count / 2).await?) / 2.0,
+    };
+
+    log::trace!("Computing percentiles...");
+    let mut percentile_objects = Vec::with_capacity(percentiles.len());
+    for percentile in percentiles {
+        let rank = (percentile / 100.0 * count as f64).ceil() as usize;
+        let score = score_at_rank(rconn, scores_key, order, count, rank.clamp(1, count) - 1).await?;
+        percentile_objects.push(PercentileObject {percentile: *percentile, score});
+    }
+
+    log::trace!("Computing mean...");
+    let mean = sum / count as f64;
+
+    log::trace!("Computing histogram...");
+    let width = (max - min) / buckets as f64;
+    let mut histogram = Vec::with_capacity(buckets);
+    if width == 0.0 {
+        histogram.push(BucketObject {min, max, count});
+    }
+    else {
+        for index in 0..buckets {
+            let bucket_min = min + width * index as f64;
+            let (bucket_max, upper) = match index == buckets - 1 {
+                true => (max, format!("{max}")),
+                false => (min + width * (index + 1) as f64, format!("({}", min + width * (index + 1) as f64)),
+            };
+            let bucket_count = rconn.zcount::<&str, f64, String, usize>(scores_key, bucket_min, upper).await
+                .map_err(outcome::redis_cmd_failed)?;
+            histogram.push(BucketObject {min: bucket_min, max: bucket_max, count: bucket_count});
+        }
+    }
+
+    Ok(RouteBoardStatsResponse {
+        count,
+        min: Some(min),
+        max: Some(max),
+        mean: Some(mean),
+        median: Some(median),
+        percentiles: percentile_objects,
+        histogram,
+    })
+}
+
+
+/// Handler for `GET /board/stats/`.
+pub(crate) async fn route_board_stats_get(
+    // Request query
+    Query(RouteBoardStatsQuery {board, percentiles, buckets}): Query<RouteBoardStatsQuery>,
+    // Redis client
+    Extension(rclient): Extension<redis::Client>,
+) -> outcome::RequestResult {
+
+    let board = board.to_kebab_lowercase();
+
+    log::trace!("Parsing requested percentiles...");
+    let percentiles = percentiles.unwrap_or_else(|| "50,90,99".to_string());
+    let percentiles = percentiles
+        .split(',')
+        .filter(|p| !p.is_empty())
+        .map(|p| p.trim().parse::<f64>().ok().filter(|p| (0.0..=100.0).contains(p)))
+        .collect::<Option<Vec<f64>>>()
+        .ok_or_else(|| (StatusCode::BAD_REQUEST, outcome::req_error!("Percentiles must be numbers between 0 and 100")))?;
+
+    log::trace!("Ensuring the number of percentiles and buckets is within limits...");
+    if percentiles.len() > 100 {
+        return Err((StatusCode::BAD_REQUEST, outcome::req_error!("Cannot request more than 100 percentiles at a time")))
+    }
+    let buckets = buckets.unwrap_or(10);
+    if !(1..=100).contains(&buckets) {
+        return Err((StatusCode::BAD_REQUEST, outcome::req_error!("Histogram must have between 1 and 100 buckets")))
+    }
+
+    log::trace!("Determining the Redis key names...");
+    let order_key = format!("board:{board}:order");
+    let scores_key = format!("board:{board}:scores");
+    let cache_key = format!(
+        "board:{board}:stats:{}:{buckets}",
+        percentiles.iter().map(|p| p.to_string()).collect::<Vec<String>>().join(",")
+    );
+
+    let mut rconn = rclient.get_connection_or_504().await?;
+
+    log::trace!("Ensuring the board exists...");
+    let order = rconn.get::<&str, String>(&order_key).await
+        .map_err(outcome::redis_cmd_failed)?;
+    let order = SortingOrder::try_from(order.as_str())
+        .map_err(|_| outcome::redis_unexpected_behaviour())?;
+
+    let cached = match *config::STATS_CACHE_SECONDS {
+        0 => None,
+        _ => {
+            log::trace!("Checking for cached statistics...");
+            rconn.get::<&str, Option<String>>(&cache_key).await
+                .map_err(outcome::redis_cmd_failed)?
+        },
+    };
+
+    let result = match cached.and_then(|c| serde_json::from_str::<RouteBoardStatsResponse>(&c).ok()) {
+        Some(result) => {
+            log::trace!("Using cached statistics.");
+            result
+        },
+        None => {
+            log::debug!("Computing statistics for {board:?}...");
+            let result = compute_stats(&mut rconn, &scores_key, order, &percentiles, buckets).await?;
+
+            if *config::STATS_CACHE_SECONDS > 0 {
+                log::trace!("Caching statistics for {} seconds...", *config::STATS_CACHE_SECONDS);
+                let serialized = serde_json::to_string(&result)
+                    .expect("statistics to be serializable");
+                rconn.set_ex::<&str, String, ()>(&cache_key, serialized, *config::STATS_CACHE_SECONDS).await
+                    .map_err(outcome::redis_cmd_failed)?;
+            }
+
+            result
+        },
+    };
+
+    Ok((StatusCode::OK, outcome::req_success!(result)))
+}
@@ -0,0 +1,215 @@
+//! Module defining routes for `/score/sync/`.
+
+use std::net::SocketAddr;
+use axum::http::{HeaderMap, StatusCode};
+use axum::extract::{ConnectInfo, Extension, Json, Query};
+use serde::Serialize;
+use serde::Deserialize;
+use crate::outcome;
+use crate::routes::batch::RouteScoreBatchResult;
+use crate::shortcuts::audit::{Actor, Origin};
+use crate::shortcuts::blobs::{BlobStore, SharedBlobStore};
+use crate::shortcuts::idempotency::IdempotencyKey;
+use crate::shortcuts::redis::RedisConnectOr504;
+use crate::shortcuts::state::BoardState;
+use crate::shortcuts::submit::{get_submission_target_at, prepare_submission, write_submissions, Submission, SubmissionTarget};
+use crate::shortcuts::token::{Authorize, CheckBoardToken};
+use crate::utils::kebab::Skewer;
+use crate::utils::schema::ScoreValue;
+use crate::config;
+
+
+/// The maximum number of scores that can be synchronized in a single request.
+const MAX_SYNC_SIZE: usize = 500;
+
+
+/// Expected query params for [`POST /score/sync/`](route_score_sync_post).
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(crate) struct RouteScoreSyncQuery {
+    /// The board to synchronize the scores to.
+    pub(crate) board: String,
+}
+
+
+/// A score achieved offline, to synchronize with [`POST /score/sync/`](route_score_sync_post).
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(crate) struct RouteScoreSyncEntry {
+    /// The name of the player who achieved the score.
+    pub(crate) player: String,
+    /// The achieved score, with one value per column if the board has multiple.
+    pub(crate) score: ScoreValue,
+    /// The UNIX timestamp at which the score was achieved, according to the clock of the client.
+    pub(crate) achieved_at: i64,
+}
+
+
+/// Ensure that a score achieved at `achieved_at` can still be synchronized, returning the timestamp to submit it at.
+///
+/// Timestamps slightly in the future are tolerated, to account for the skew between the clocks of the client and of the server.
+fn check_achieved_at_or_422(achieved_at: i64, now: i64) -> Result<i64, outcome::RequestTuple> {
+    log::trace!("Ensuring the score was achieved within the allowed window...");
+    if achieved_at > now.saturating_add(*config::SYNC_MAX_CLOCK_SKEW_SECONDS) {
+        return Err((StatusCode::UNPROCESSABLE_ENTITY, outcome::req_error!("Score cannot have been achieved in the future")))
+    }
+    if achieved_at < now.saturating_sub(*config::SYNC_MAX_BACKDATE_SECONDS) {
+        return Err((StatusCode::UNPROCESSABLE_ENTITY, outcome::req_error!("Score was achieved too long ago to be synchronized")))
+    }
+    Ok(achieved_at.min(now))
+}
+
+
+/// Ensure that `actor` can still synchronize scores at `now` to a board in the given `state`, returning `423 Locked` otherwise.
+///
+/// Once the board has been closed for longer than the grace period, scores achieved before its closing can only be synchronized with the board token, so that players cannot keep submitting them by backdating them.
+fn check_closing_or_423(state: &BoardState, now: i64, actor: Actor) -> Result<(), outcome::RequestTuple> {
+    log::trace!("Ensuring the board has not been closed for too long...");
+    let closed = state.closes_at
+        .filter(|closes_at| now >= closes_at.saturating_add(*config::SYNC_CLOSE_GRACE_SECONDS))
+        .is_some();
+    match (closed, actor) {
+        (false, _) | (true, Actor::Board) => Ok(()),
+        (true, _) => Err((StatusCode::LOCKED, outcome::req_error!("Board is closed, only the board token can synchronize scores anymore"))),
+    }
+}
+
+
+/// Check every entry as [`PUT /score/`](crate::routes::score::route_score_put) would have at the time it was achieved, then write all the accepted ones together.
+///
+/// Entries that are refused are reported in the results, so this only fails if writing the accepted ones did.
+async fn sync_scores(rconn: &mut redis::aio::Connection, blobs: &dyn BlobStore, board: &str, token: &str, addr: SocketAddr, entries: Vec<RouteScoreSyncEntry>) -> outcome::RequestResult {
+    let now = chrono::Utc::now().timestamp();
+    let mut prepared: Vec<Result<(SubmissionTarget, Submission), outcome::RequestTuple>> = vec![];
+
+    for RouteScoreSyncEntry {player, score, achieved_at} in entries {
+        let display_name = player.clone();
+        let player = player.to_kebab_lowercase_with(&config::PLAYER_NAMES);
+
+        let submission = async {
+            log::trace!("Ensuring the player name follows the name policy...");
+            config::PLAYER_NAME_POLICY.check(&display_name, &player)
+                .map_err(|violation| (StatusCode::UNPROCESSABLE_ENTITY, outcome::req_error!((violation.message()))))?;
+
+            let actor = rconn.check_board_or_player_token_or_403(board, &player, token).await?;
+            let origin = Origin::new(actor, token, addr);
+
+            let at = check_achieved_at_or_422(achieved_at, now)?;
+            let target = get_submission_target_at(rconn, board, at).await?;
+            check_closing_or_423(&target.state, now, actor)?;
+
+            let submission = prepare_submission(rconn, &target, player, display_name, &score, origin).await?;
+            Ok((target, submission))
+        }.await;
+
+        prepared.push(submission);
+    }
+
+    let accepted: Vec<(&SubmissionTarget, &Submission)> = prepared.iter()
+        .filter_map(|submission| submission.as_ref().ok())
+        .map(|(target, submission)| (target, submission))
+        .collect();
+
+    log::debug!("Synchronizing {} of {} scores to {board:?}...", accepted.len(), prepared.len());
+    let mut written = write_submissions(rconn, blobs, &accepted).await
+        .map_err(outcome::redis_cmd_failed)?
+        .into_iter();
+
+    let results = prepared.into_iter()
+        .map(|submission| match submission {
+            Ok((target, submission)) => {
+                let written = written.next()
+                    .expect("every accepted submission to have been written");
+                let changed = written.changed;
+                let response = submission.into_response(&target, written);
+                match changed {
+                    true => RouteScoreBatchResult::Improved(response),
+                    false => RouteScoreBatchResult::Accepted(response),
+                }
+            },
+            Err(error) => RouteScoreBatchResult::from(error),
+        })
+        .collect::<Vec<RouteScoreBatchResult>>();
+
+    Ok((StatusCode::OK, outcome::req_success!(results)))
+}
+
+
+/// Handler for `POST /score/sync/`.
+///
+/// Entries achieved before the end of a season are submitted to its archive instead of the live board, and are ranked there.
+///
+/// Results are returned in the same order as the entries.
+///
+/// If the request has an `Idempotency-Key` header, the response is stored, and replayed for retries of the same request.
+pub(crate) async fn route_score_sync_post(
+    // Redis client
+    Extension(rclient): Extension<redis::Client>,
+    // Blob store
+    Extension(blobs): Extension<SharedBlobStore>,
+    // Client address
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    // Request headers
+    headers: HeaderMap,
+    // Request query
+    Query(RouteScoreSyncQuery {board}): Query<RouteScoreSyncQuery>,
+    // Request body
+    Json(entries): Json<Vec<RouteScoreSyncEntry>>,
+) -> outcome::RequestResult {
+    let board = board.to_kebab_lowercase();
+
+    log::trace!("Ensuring the batch is within limits...");
+    if entries.len() > MAX_SYNC_SIZE {
+        return Err((StatusCode::BAD_REQUEST, outcome::req_error!("Cannot synchronize more than 500 scores at once")))
+    }
+
+    let token = headers.get_authorization_or_401("Bearer")?;
+    let idempotency = IdempotencyKey::from_headers_or_400(&headers, &board, token, &entries)?;
+    let mut rconn = rclient.get_connection_or_504().await?;
+
+    let idempotency = match idempotency {
+        Some(idempotency) => match idempotency.reserve_or_replay(&mut rconn).await? {
+            Some(replay) => return Ok(replay),
+            None => Some(idempotency),
+        },
+        None => None,
+    };
+
+    let result = sync_scores(&mut rconn, &*blobs, &board, token, addr, entries).await;
+
+    if let Some(idempotency) = idempotency {
+        if let Err(err) = idempotency.complete(&mut rconn, &result).await {
+            log::error!("{err:#?}");
+        }
+    }
+
+    result
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn closing_at(closes_at: i64) -> BoardState {
+        BoardState {locked: false, opens_at: None, closes_at: Some(closes_at)}
+    }
+
+    #[test]
+    fn sync_before_closing() {
+        assert!(check_closing_or_423(&closing_at(1000), 999, Actor::Player).is_ok());
+        assert!(check_closing_or_423(&closing_at(1000), 999, Actor::Board).is_ok());
+        assert!(check_closing_or_423(&BoardState {locked: false, opens_at: None, closes_at: None}, 999, Actor::Player).is_ok());
+    }
+
+    #[test]
+    fn sync_after_closing() {
+        let (status, _) = check_closing_or_423(&closing_at(1000), 1000, Actor::Player).unwrap_err();
+        assert_eq!(status, StatusCode::LOCKED);
+        assert!(check_closing_or_423(&closing_at(1000), 1000 + 86400, Actor::Player).is_err());
+    }
+
+    #[test]
+    fn sync_after_closing_with_board_token() {
+        assert!(check_closing_or_423(&closing_at(1000), 1000, Actor::Board).is_ok());
+        assert!(check_closing_or_423(&closing_at(1000), 1000 + 86400, Actor::Board).is_ok());
+    }
+}
@@ -0,0 +1,245 @@
+//! Module defining routes for `/board/webhooks/`.
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use axum::http::{HeaderMap, StatusCode};
+use axum::extract::{ConnectInfo, Extension, Json, Query};
+use redis::AsyncCommands;
+use serde::Serialize;
+use serde::Deserialize;
+use crate::outcome;
+use crate::shortcuts::audit::{audit_to, Actor, Origin};
+use crate::shortcuts::redis::RedisConnectOr504;
+use crate::shortcuts::token::{Authorize, CheckBoardToken, Generate};
+use crate::shortcuts::webhooks::{delivery_log_key, resolve_public_url, webhooks_key, DeliveryLogEntry, WebhookFilter, WebhookObject};
+use crate::utils::kebab::Skewer;
+use crate::utils::token::SecureToken;
+
+
+/// Expected query params for [`GET /board/webhooks/`](route_board_webhooks_get) and [`POST /board/webhooks/`](route_board_webhooks_post).
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(crate) struct RouteBoardWebhooksQuery {
+    /// The board the webhooks are subscribed to.
+    pub(crate) board: String,
+}
+
+
+/// Expected query params for [`DELETE /board/webhooks/`](route_board_webhooks_delete) and [`GET /board/webhooks/deliveries/`](route_board_webhooks_deliveries_get).
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(crate) struct RouteBoardWebhookQuery {
+    /// The board the webhook is subscribed to.
+    pub(crate) board: String,
+    /// The id of the webhook.
+    pub(crate) id: String,
+}
+
+
+/// Expected body for [`POST /board/webhooks/`](route_board_webhooks_post).
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(crate) struct RouteBoardWebhooksBody {
+    /// The URL the events should be `POST`ed to.
+    pub(crate) url: String,
+    /// The kinds of events the webhook should be notified about.
+    pub(crate) filters: Vec<WebhookFilter>,
+    /// How many positions count as the top of the board for [`WebhookFilter::TopEntry`].
+    #[serde(default = "default_top")]
+    pub(crate) top: usize,
+}
+
+/// By default, only the first position counts as the top of the board.
+fn default_top() -> usize {
+    1
+}
+
+
+/// A webhook, as returned to the board owner.
+///
+/// The secret is only ever revealed when the webhook is created.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(crate) struct WebhookInfo {
+    /// The URL the events are `POST`ed to.
+    pub(crate) url: String,
+    /// The kinds of events the webhook is subscribed to.
+    pub(crate) filters: Vec<WebhookFilter>,
+    /// How many positions count as the top of the board for [`WebhookFilter::TopEntry`].
+    pub(crate) top: usize,
+}
+
+impl From<WebhookObject> for WebhookInfo {
+    fn from(webhook: WebhookObject) -> Self {
+        Self {url: webhook.url, filters: webhook.filters, top: webhook.top}
+    }
+}
+
+
+/// Response of [`POST /board/webhooks/`](route_board_webhooks_post).
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(crate) struct RouteBoardWebhooksPostResponse {
+    /// The id of the created webhook.
+    pub(crate) id: String,
+    /// The secret the payloads delivered to the webhook will be signed with.
+    pub(crate) secret: String,
+}
+
+
+/// Handler for `GET /board/webhooks/`.
+pub(crate) async fn route_board_webhooks_get(
+    // Redis client
+    Extension(rclient): Extension<redis::Client>,
+    // Request headers
+    headers: HeaderMap,
+    // Request query
+    Query(RouteBoardWebhooksQuery {board}): Query<RouteBoardWebhooksQuery>,
+) -> outcome::RequestResult {
+    let board = board.to_kebab_lowercase();
+
+    let token = headers.get_authorization_or_401("Bearer")?;
+    let mut rconn = rclient.get_connection_or_504().await?;
+
+    rconn.check_board_token_or_403(&board, token).await?;
+
+    log::trace!("Retrieving webhooks...");
+    let webhooks = rconn.hgetall::<String, HashMap<String, String>>(webhooks_key(&board)).await
+        .map_err(outcome::redis_cmd_failed)?
+        .into_iter()
+        .map(|(id, webhook)| serde_json::from_str::<WebhookObject>(&webhook).map(|webhook| (id, WebhookInfo::from(webhook))))
+        .collect::<Result<HashMap<String, WebhookInfo>, serde_json::Error>>()
+        .map_err(|_| outcome::redis_unexpected_behaviour())?;
+
+    Ok((StatusCode::OK, outcome::req_success!(webhooks)))
+}
+
+
+/// Handler for `POST /board/webhooks/`.
+pub(crate) async fn route_board_webhooks_post(
+    // Redis client
+    Extension(rclient): Extension<redis::Client>,
+    // Client address
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    // Request headers
+    headers: HeaderMap,
+    // Request query
+    Query(RouteBoardWebhooksQuery {board}): Query<RouteBoardWebhooksQuery>,
+    // Request body
+    Json(RouteBoardWebhooksBody {url, filters, top}): Json<RouteBoardWebhooksBody>,
+) -> outcome::RequestResult {
+    let board = board.to_kebab_lowercase();
+
+    log::trace!("Ensuring the webhook is valid...");
+    if !(url.starts_with("https://") || url.starts_with("http://")) {
+        return Err((StatusCode::BAD_REQUEST, outcome::req_error!("Webhook URL must use the http or https scheme")))
+    }
+    if filters.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, outcome::req_error!("Webhook must subscribe to at least one event")))
+    }
+    if top == 0 {
+        return Err((StatusCode::BAD_REQUEST, outcome::req_error!("Top must be at least 1")))
+    }
+
+    log::trace!("Ensuring the webhook URL points to a public address...");
+    resolve_public_url(&url).await
+        .map_err(|message| (StatusCode::BAD_REQUEST, outcome::req_error!(message)))?;
+
+    let token = headers.get_authorization_or_401("Bearer")?;
+    let mut rconn = rclient.get_connection_or_504().await?;
+
+    rconn.check_board_token_or_403(&board, token).await?;
+    let origin = Origin::new(Actor::Board, token, addr);
+
+    let id = SecureToken::new_or_500()?.0;
+    let secret = SecureToken::new_or_500()?.0;
+    let webhook = WebhookObject {url, secret: secret.clone(), filters, top};
+
+    log::debug!("Subscribing {:?} to {board:?}...", webhook.url);
+    let mut pipe = redis::pipe();
+    pipe.atomic();
+    pipe.hset(webhooks_key(&board), &id, serde_json::to_string(&webhook).expect("webhook to be serializable")).ignore();
+    audit_to(&mut pipe, Some(&board), &origin, "WebhookCreated", &[
+        ("webhook", id.clone()),
+        ("url", webhook.url.clone()),
+    ]);
+    pipe.query_async::<redis::aio::Connection, ()>(&mut rconn).await
+        .map_err(outcome::redis_cmd_failed)?;
+
+    let result = RouteBoardWebhooksPostResponse {id, secret};
+
+    Ok((StatusCode::CREATED, outcome::req_success!(result)))
+}
+
+
+/// Handler for `DELETE /board/webhooks/`.
+///
+/// Pending deliveries to the webhook are discarded when they are next attempted.
+pub(crate) async fn route_board_webhooks_delete(
+    // Redis client
+    Extension(rclient): Extension<redis::Client>,
+    // Client address
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    // Request headers
+    headers: HeaderMap,
+    // Request query
+    Query(RouteBoardWebhookQuery {board, id}): Query<RouteBoardWebhookQuery>,
+) -> Result<StatusCode, outcome::RequestTuple> {
+    let board = board.to_kebab_lowercase();
+
+    let token = headers.get_authorization_or_401("Bearer")?;
+    let mut rconn = rclient.get_connection_or_504().await?;
+
+    rconn.check_board_token_or_403(&board, token).await?;
+    let origin = Origin::new(Actor::Board, token, addr);
+
+    log::debug!("Deleting webhook {id:?} of {board:?}...");
+    let removed = rconn.hdel::<String, &str, bool>(webhooks_key(&board), &id).await
+        .map_err(outcome::redis_cmd_failed)?;
+
+    if !removed {
+        return Err((StatusCode::NOT_FOUND, outcome::req_error!("No such webhook")))
+    }
+
+    let mut pipe = redis::pipe();
+    pipe.del(delivery_log_key(&board, &id)).ignore();
+    audit_to(&mut pipe, Some(&board), &origin, "WebhookDeleted", &[
+        ("webhook", id.clone()),
+    ]);
+    pipe.query_async::<redis::aio::Connection, ()>(&mut rconn).await
+        .map_err(outcome::redis_cmd_failed)?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+
+/// Handler for `GET /board/webhooks/deliveries/`.
+///
+/// Returns the latest delivery attempts to the webhook, most recent first.
+pub(crate) async fn route_board_webhooks_deliveries_get(
+    // Redis client
+    Extension(rclient): Extension<redis::Client>,
+    // Request headers
+    headers: HeaderMap,
+    // Request query
+    Query(RouteBoardWebhookQuery {board, id}): Query<RouteBoardWebhookQuery>,
+) -> outcome::RequestResult {
+    let board = board.to_kebab_lowercase();
+
+    let token = headers.get_authorization_or_401("Bearer")?;
+    let mut rconn = rclient.get_connection_or_504().await?;
+
+    rconn.check_board_token_or_403(&board, token).await?;
+
+    log::trace!("Ensuring the webhook exists...");
+    let exists = rconn.hexists::<String, &str, bool>(webhooks_key(&board), &id).await
+        .map_err(outcome::redis_cmd_failed)?;
+    if !exists {
+        return Err((StatusCode::NOT_FOUND, outcome::req_error!("No such webhook")))
+    }
+
+    log::trace!("Retrieving delivery log...");
+    let deliveries = rconn.lrange::<String, Vec<String>>(delivery_log_key(&board, &id), 0, -1).await
+        .map_err(outcome::redis_cmd_failed)?
+        .iter()
+        .map(|entry| serde_json::from_str::<DeliveryLogEntry>(entry))
+        .collect::<Result<Vec<DeliveryLogEntry>, serde_json::Error>>()
+        .map_err(|_| outcome::redis_unexpected_behaviour())?;
+
+    Ok((StatusCode::OK, outcome::req_success!(deliveries)))
+}
@@ -0,0 +1,94 @@
+//! Module defining how actions are recorded in the audit logs.
+
+use std::net::SocketAddr;
+use serde::Serialize;
+use serde::Deserialize;
+use crate::config;
+use crate::shortcuts::token::TOKEN_ID_SECRET;
+use crate::utils::token::token_id;
+
+
+/// Key of the stream containing the audit log of all boards.
+pub(crate) const GLOBAL_AUDIT_KEY: &str = "audit";
+
+
+/// Who performed an audited action.
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+pub(crate) enum Actor {
+    /// A holder of the token used to create boards.
+    Creator,
+    /// A holder of the board token.
+    Board,
+    /// A registered player, using their player token.
+    Player,
+    /// A holder of the admin token of the server.
+    Admin,
+}
+
+/// How the [`Actor`] is stored in [Redis].
+impl From<Actor> for &str {
+    fn from(actor: Actor) -> Self {
+        match actor {
+            Actor::Creator => "Creator",
+            Actor::Board   => "Board",
+            Actor::Player  => "Player",
+            Actor::Admin   => "Admin",
+        }
+    }
+}
+
+
+/// Where an audited action came from.
+#[derive(Clone, Debug)]
+pub(crate) struct Origin {
+    /// Who performed the action.
+    pub(crate) actor: Actor,
+    /// The [id](token_id) of the token used to perform the action.
+    pub(crate) token: String,
+    /// The address of the client who performed the action.
+    pub(crate) addr: SocketAddr,
+}
+
+impl Origin {
+    /// Create a new [`Origin`] from the token used to perform the action.
+    pub(crate) fn new(actor: Actor, token: &str, addr: SocketAddr) -> Self {
+        Self {actor, token: token_id(&TOKEN_ID_SECRET, token), addr}
+    }
+}
+
+
+/// Get the key of the stream containing the audit log of `board`.
+pub(crate) fn audit_key(board: Option<&str>) -> String {
+    match board {
+        Some(board) => format!("board:{board}:audit"),
+        None => GLOBAL_AUDIT_KEY.to_string(),
+    }
+}
+
+
+/// Queue the commands recording `action` in the audit logs in the given pipeline.
+///
+/// The entry is stored both in the audit log of `board`, if any, and in the [global one](GLOBAL_AUDIT_KEY), along with its [`Origin`] and any additional `fields`.
+///
+/// Both logs are trimmed to approximately `AUDIT_MAX_LENGTH` entries.
+pub(crate) fn audit_to(pipe: &mut redis::Pipeline, board: Option<&str>, origin: &Origin, action: &str, fields: &[(&str, String)]) {
+    let ip = origin.addr.ip().to_string();
+
+    let mut entry: Vec<(&str, &str)> = vec![
+        ("action", action),
+        ("actor", origin.actor.into()),
+        ("token", &origin.token),
+        ("ip", &ip),
+    ];
+    if let Some(board) = board {
+        entry.push(("board", board));
+    }
+    entry.extend(fields.iter().map(|(field, value)| (*field, value.as_str())));
+
+    let maxlen = redis::streams::StreamMaxlen::Approx(*config::AUDIT_MAX_LENGTH);
+
+    if board.is_some() {
+        pipe.xadd_maxlen(audit_key(board), maxlen, "*", &entry).ignore();
+    }
+    pipe.xadd_maxlen(GLOBAL_AUDIT_KEY, maxlen, "*", &entry).ignore();
+}
@@ -0,0 +1,170 @@
+use redis::AsyncCommands;
+use serde::Serialize;
+use serde::Deserialize;
+use crate::outcome;
+use crate::shortcuts::exact::{exact_scores_key, get_rank_of, sum_scores_key, EXACT_LUA};
+use crate::utils::kind::{ScoreKind, ScoreNumber};
+use crate::utils::sorting::SortingOrder;
+
+
+lazy_static::lazy_static! {
+    /// Script moving the score of a player from a sorted set to another in a single atomic step, along with its exact value, if it has one.
+    ///
+    /// - `KEYS[1]`: the key to move the score from
+    /// - `KEYS[2]`: the key to move the score to
+    /// - `KEYS[3]`: the key to move the exact value of the score from
+    /// - `KEYS[4]`: the key to move the exact value of the score to
+    /// - `KEYS[5]`: the running sum of the scores to move the score from
+    /// - `KEYS[6]`: the running sum of the scores to move the score to
+    /// - `ARGV[1]`: the [`ZADD`](https://redis.io/commands/zadd/) mode to use when inserting the score
+    /// - `ARGV[2]`: the player whose score should be moved
+    ///
+    /// The running sum of the scores of `KEYS[1]` is deleted, as removing the score invalidates it.
+    ///
+    /// Returns `1` if the score was moved, or `0` if the player had no score to move.
+    static ref MOVE_SCRIPT: redis::Script = redis::Script::new(&[EXACT_LUA, r#"
+        local score = redis.call("ZSCORE", KEYS[1], ARGV[2])
+        if not score then
+            return 0
+        end
+        local exact = redis.call("HGET", KEYS[3], ARGV[2])
+        redis.call("ZREM", KEYS[1], ARGV[2])
+        redis.call("HDEL", KEYS[3], ARGV[2])
+        redis.call("DEL", KEYS[5])
+        insert(KEYS[2], KEYS[4], KEYS[6], ARGV[1], score, exact or "", ARGV[2])
+        return 1
+    "#].concat());
+}
+
+
+/// Key of the hash containing the bans valid on all boards.
+pub(crate) const GLOBAL_BANS_KEY: &str = "bans";
+
+
+/// How severely a player is banned.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub(crate) enum BanKind {
+    /// Submissions are accepted, but the scores are hidden from everyone but the player.
+    ShadowBan,
+    /// Submissions are rejected.
+    Ban,
+}
+
+
+/// A ban of a player, as a serializable struct.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(crate) struct BanObject {
+    /// How severely the player is banned.
+    pub(crate) kind: BanKind,
+    /// Why the player was banned.
+    pub(crate) reason: String,
+    /// The UNIX timestamp at which the player was banned.
+    pub(crate) banned_at: i64,
+}
+
+
+/// Get the key of the hash containing the bans of `board`, or the [global one](GLOBAL_BANS_KEY) if [`None`].
+pub(crate) fn bans_key(board: Option<&str>) -> String {
+    match board {
+        Some(board) => format!("board:{board}:bans"),
+        None => GLOBAL_BANS_KEY.to_string(),
+    }
+}
+
+
+/// Get the key of the sorted set containing the hidden scores of the shadow-banned players of `board`.
+pub(crate) fn shadow_scores_key(board: &str) -> String {
+    format!("board:{board}:shadow")
+}
+
+
+/// Get the names of all the boards, so that global bans can be applied to each of them.
+pub(crate) async fn get_all_boards(rconn: &mut redis::aio::Connection) -> Result<Vec<String>, redis::RedisError> {
+    log::trace!("Listing all boards...");
+
+    let mut keys = vec![];
+    let mut iter = rconn.scan_match::<&str, String>("board:*:order").await?;
+    while let Some(key) = iter.next_item().await {
+        keys.push(key);
+    }
+
+    let boards = keys.into_iter()
+        .filter_map(|key| key.strip_prefix("board:")?.strip_suffix(":order").map(str::to_string))
+        .filter(|board| !board.contains(':'))
+        .collect::<Vec<String>>();
+    log::trace!("Boards are: {boards:?}");
+
+    Ok(boards)
+}
+
+
+/// Get the most severe [`BanKind`] applying to `player` on `board`, either because of a ban on the board or of a global one.
+pub(crate) async fn get_ban_kind(rconn: &mut redis::aio::Connection, board: &str, player: &str) -> Result<Option<BanKind>, outcome::RequestTuple> {
+    log::trace!("Checking if {player:?} is banned from {board:?}...");
+
+    let (board_ban, global_ban) = redis::pipe()
+        .hget(bans_key(Some(board)), player)
+        .hget(GLOBAL_BANS_KEY, player)
+        .query_async::<redis::aio::Connection, (Option<String>, Option<String>)>(rconn).await
+        .map_err(outcome::redis_cmd_failed)?;
+
+    let kind = [board_ban, global_ban].into_iter()
+        .flatten()
+        .map(|ban| serde_json::from_str::<BanObject>(&ban).map(|ban| ban.kind))
+        .collect::<Result<Vec<BanKind>, serde_json::Error>>()
+        .map_err(|_| outcome::redis_unexpected_behaviour())?
+        .into_iter()
+        .max();
+    log::trace!("Ban is: {kind:?}");
+
+    Ok(kind)
+}
+
+
+/// Move the score of `player` on `board` to the hidden scores of the shadow-banned players.
+pub(crate) async fn hide_score(rconn: &mut redis::aio::Connection, board: &str, player: &str, order: SortingOrder) -> Result<bool, redis::RedisError> {
+    log::trace!("Hiding the score of {player:?} on {board:?}...");
+
+    let scores_key = format!("board:{board}:scores");
+    let shadow_key = shadow_scores_key(board);
+
+    MOVE_SCRIPT
+        .key(&scores_key)
+        .key(&shadow_key)
+        .key(exact_scores_key(&scores_key))
+        .key(exact_scores_key(&shadow_key))
+        .key(sum_scores_key(&scores_key))
+        .key(sum_scores_key(&shadow_key))
+        .arg(order.zadd_mode())
+        .arg(player)
+        .invoke_async::<redis::aio::Connection, bool>(rconn).await
+}
+
+
+/// Move the hidden score of `player` on `board` back to the live scores, keeping the best one if the player has both.
+pub(crate) async fn restore_score(rconn: &mut redis::aio::Connection, board: &str, player: &str, order: SortingOrder) -> Result<bool, redis::RedisError> {
+    log::trace!("Restoring the hidden score of {player:?} on {board:?}...");
+
+    let scores_key = format!("board:{board}:scores");
+    let shadow_key = shadow_scores_key(board);
+
+    MOVE_SCRIPT
+        .key(&shadow_key)
+        .key(&scores_key)
+        .key(exact_scores_key(&shadow_key))
+        .key(exact_scores_key(&scores_key))
+        .key(sum_scores_key(&shadow_key))
+        .key(sum_scores_key(&scores_key))
+        .arg(order.zadd_mode())
+        .arg(player)
+        .invoke_async::<redis::aio::Connection, bool>(rconn).await
+}
+
+
+/// Get the rank the hidden `score` of `player` on `board` would have if it was visible, so that shadow-banned players do not notice they are.
+pub(crate) async fn get_shadow_rank(rconn: &mut redis::aio::Connection, board: &str, kind: ScoreKind, order: SortingOrder, player: &str, score: ScoreNumber) -> Result<usize, redis::RedisError> {
+    let scores_key = format!("board:{board}:scores");
+
+    log::trace!("Ranking the hidden score {score:?} among the visible ones...");
+    get_rank_of(rconn, &scores_key, kind, order, player, score).await
+}
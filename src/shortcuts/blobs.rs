@@ -0,0 +1,147 @@
+//! Module defining where large binary objects, such as replays, are stored.
+
+use std::io;
+use std::path::PathBuf;
+use std::sync::Arc;
+use async_trait::async_trait;
+use axum::body::Bytes;
+use axum::http::StatusCode;
+use redis::AsyncCommands;
+use crate::outcome;
+use crate::config;
+
+
+/// A store of binary objects, identified by opaque ids made of letters and digits.
+#[async_trait]
+pub(crate) trait BlobStore: Send + Sync {
+    /// Store `data` as the blob with the given `id`, replacing any previous one.
+    async fn put(&self, id: &str, data: Bytes) -> io::Result<()>;
+
+    /// Retrieve the blob with the given `id`, or [`None`] if there is none.
+    async fn get(&self, id: &str) -> io::Result<Option<Bytes>>;
+
+    /// Delete the blob with the given `id`, if it exists.
+    async fn delete(&self, id: &str) -> io::Result<()>;
+}
+
+
+/// The [`BlobStore`] shared by all handlers.
+pub(crate) type SharedBlobStore = Arc<dyn BlobStore>;
+
+
+/// Which [`BlobStore`] to use.
+#[derive(Copy, Clone, Debug)]
+pub(crate) enum BlobStoreKind {
+    /// Store blobs as files in a directory of the local filesystem.
+    Filesystem,
+    /// Store blobs as strings in [Redis].
+    Redis,
+}
+
+/// How the [`BlobStoreKind`] is configured.
+impl TryFrom<&str> for BlobStoreKind {
+    type Error = ();
+
+    fn try_from(val: &str) -> Result<Self, Self::Error> {
+        match val {
+            "Filesystem" => Ok(Self::Filesystem),
+            "Redis"      => Ok(Self::Redis),
+            _ => Err(())
+        }
+    }
+}
+
+
+/// A [`BlobStore`] keeping blobs as files in a directory.
+pub(crate) struct FilesystemBlobStore {
+    /// The directory containing the blobs.
+    pub(crate) root: PathBuf,
+}
+
+#[async_trait]
+impl BlobStore for FilesystemBlobStore {
+    async fn put(&self, id: &str, data: Bytes) -> io::Result<()> {
+        tokio::fs::create_dir_all(&self.root).await?;
+        tokio::fs::write(self.root.join(id), data).await
+    }
+
+    async fn get(&self, id: &str) -> io::Result<Option<Bytes>> {
+        match tokio::fs::read(self.root.join(id)).await {
+            Ok(data) => Ok(Some(Bytes::from(data))),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    async fn delete(&self, id: &str) -> io::Result<()> {
+        match tokio::fs::remove_file(self.root.join(id)).await {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+            _ => Ok(()),
+        }
+    }
+}
+
+
+/// A [`BlobStore`] keeping blobs in [Redis], for deployments without persistent local storage.
+pub(crate) struct RedisBlobStore {
+    /// The client to connect to Redis with.
+    pub(crate) rclient: redis::Client,
+}
+
+impl RedisBlobStore {
+    /// Get the key of the string containing the blob with the given `id`.
+    fn blob_key(id: &str) -> String {
+        format!("blobs:{id}")
+    }
+
+    /// Report a [`redis::RedisError`] as an [`io::Error`], like the other stores would.
+    fn redis_failed(err: redis::RedisError) -> io::Error {
+        io::Error::other(err)
+    }
+}
+
+#[async_trait]
+impl BlobStore for RedisBlobStore {
+    async fn put(&self, id: &str, data: Bytes) -> io::Result<()> {
+        let mut rconn = self.rclient.get_async_connection().await
+            .map_err(Self::redis_failed)?;
+        rconn.set::<String, &[u8], ()>(Self::blob_key(id), &data).await
+            .map_err(Self::redis_failed)?;
+        Ok(())
+    }
+
+    async fn get(&self, id: &str) -> io::Result<Option<Bytes>> {
+        let mut rconn = self.rclient.get_async_connection().await
+            .map_err(Self::redis_failed)?;
+        let data = rconn.get::<String, Option<Vec<u8>>>(Self::blob_key(id)).await
+            .map_err(Self::redis_failed)?;
+        Ok(data.map(Bytes::from))
+    }
+
+    async fn delete(&self, id: &str) -> io::Result<()> {
+        let mut rconn = self.rclient.get_async_connection().await
+            .map_err(Self::redis_failed)?;
+        rconn.del::<String, ()>(Self::blob_key(id)).await
+            .map_err(Self::redis_failed)?;
+        Ok(())
+    }
+}
+
+
+/// Create the [`BlobStore`] selected by the `REPLAYS_STORE` environment variable.
+pub(crate) fn blob_store_from_config(rclient: &redis::Client) -> SharedBlobStore {
+    match *config::REPLAYS_STORE {
+        BlobStoreKind::Filesystem => Arc::new(FilesystemBlobStore {root: config::REPLAYS_DIR.clone()}),
+        BlobStoreKind::Redis => Arc::new(RedisBlobStore {rclient: rclient.clone()}),
+    }
+}
+
+
+/// An operation on the [`BlobStore`] failed.
+pub(crate) fn blob_store_failed(err: io::Error) -> outcome::RequestTuple {
+    log::error!("{err:#?}");
+    (
+        StatusCode::BAD_GATEWAY,
+        outcome::req_error!("Could not access the blob store")
+    )
+}
@@ -0,0 +1,61 @@
+//! Module defining how the settings of boards are retrieved.
+
+use redis::AsyncCommands;
+use crate::outcome;
+use crate::utils::kind::ScoreKind;
+use crate::utils::mode::BoardMode;
+use crate::utils::schema::ScoreSchema;
+
+
+/// Retrieve the [`BoardMode`] of the given board.
+///
+/// Boards created before modes were introduced have no mode stored, and are treated as [`BoardMode::Players`] boards.
+pub(crate) async fn get_board_mode(rconn: &mut redis::aio::Connection, board: &str) -> Result<BoardMode, outcome::RequestTuple> {
+    let mode_key = format!("board:{board}:mode");
+
+    log::trace!("Determining board mode...");
+    let mode = rconn.get::<&str, Option<String>>(&mode_key).await
+        .map_err(outcome::redis_cmd_failed)?
+        .map(|mode| BoardMode::try_from(mode.as_str()))
+        .transpose()
+        .map_err(|_| outcome::redis_unexpected_behaviour())?
+        .unwrap_or_default();
+    log::trace!("Board mode is: {mode:?}");
+
+    Ok(mode)
+}
+
+
+/// Retrieve the [`ScoreKind`] of the given board.
+///
+/// Boards created before score kinds were introduced have no kind stored, and are treated as [`ScoreKind::Float`] boards.
+pub(crate) async fn get_score_kind(rconn: &mut redis::aio::Connection, board: &str) -> Result<ScoreKind, outcome::RequestTuple> {
+    let kind_key = format!("board:{board}:kind");
+
+    log::trace!("Determining score kind...");
+    let kind = rconn.get::<&str, Option<String>>(&kind_key).await
+        .map_err(outcome::redis_cmd_failed)?
+        .map(|kind| ScoreKind::try_from(kind.as_str()))
+        .transpose()
+        .map_err(|_| outcome::redis_unexpected_behaviour())?
+        .unwrap_or_default();
+    log::trace!("Score kind is: {kind:?}");
+
+    Ok(kind)
+}
+
+
+/// Retrieve the [`ScoreSchema`] of the given board, if it has one.
+pub(crate) async fn get_score_schema(rconn: &mut redis::aio::Connection, board: &str) -> Result<Option<ScoreSchema>, outcome::RequestTuple> {
+    let schema_key = format!("board:{board}:schema");
+
+    log::trace!("Determining score schema...");
+    let schema = rconn.get::<&str, Option<String>>(&schema_key).await
+        .map_err(outcome::redis_cmd_failed)?
+        .map(|schema| serde_json::from_str::<ScoreSchema>(&schema))
+        .transpose()
+        .map_err(|_| outcome::redis_unexpected_behaviour())?;
+    log::trace!("Score schema is: {schema:?}");
+
+    Ok(schema)
+}
@@ -0,0 +1,416 @@
+//! Module defining how the scores of [`ScoreKind::Integer`] boards are stored exactly.
+//!
+//! [Redis] sorts scores as [`f64`]s, which round integers beyond `2^53`: every sorted set of scores of such boards is paired with a hash containing the exact value of each of its scores, while the sorted set keeps their closest [`f64`] for sorting.
+
+use redis::AsyncCommands;
+use crate::utils::kind::{ScoreKind, ScoreNumber};
+use crate::utils::sorting::SortingOrder;
+
+
+/// Lua functions shared by the scripts writing and ranking scores.
+///
+/// - `compare(a, b)`: compare two integers written as decimal strings, returning `-1`, `0` or `1`
+/// - `compare_scores(a_score, a_value, b_score, b_value)`: compare two scores by their approximation, then by their exact values, if both have one
+/// - `insert(scores, exact, sum, mode, score, value, member)`: write the `score` of `member` to the sorted set `scores`, emulating the [`ZADD`](https://redis.io/commands/zadd/) `mode`, one of `GT`, `LT`, `NX` or `XX`; if `value` is not empty, it is the exact value of the score as a decimal string, which is written to the hash `exact` and compared exactly with the current one; returns `1` if the score was written, or `0` otherwise, like `ZADD CH` would; the [running sum](sum_scores_key) of the scores at `sum` is kept up to date, if `sum` is not `nil` and exists
+/// - `rank(scores, exact, rev, score, value, member)`: get the zero-based rank of `member` with the given `score` and exact `value` in the sorted set `scores`, or the rank it would have if its score was visible
+/// - `range(scores, exact, rev, start, stop)`: get the scores between the ranks `start` and `stop`, like `ZRANGE WITHSCORES` would
+///
+/// Both `rank` and `range` break the ties of approximations beyond `2^53` with the exact values in the hash `exact`, or with the name of the members like [Redis] does if `exact` is `nil`.
+pub(crate) const EXACT_LUA: &str = r#"
+    local INEXACT = 2 ^ 53
+
+    local function compare(a, b)
+        local a_negative = a:sub(1, 1) == "-"
+        local b_negative = b:sub(1, 1) == "-"
+        if a_negative ~= b_negative then
+            return a_negative and -1 or 1
+        end
+        local sign = a_negative and -1 or 1
+        if #a ~= #b then
+            return (#a < #b and -1 or 1) * sign
+        end
+        if a == b then
+            return 0
+        end
+        return (a < b and -1 or 1) * sign
+    end
+
+    local function compare_scores(a_score, a_value, b_score, b_value)
+        a_score, b_score = tonumber(a_score), tonumber(b_score)
+        if a_score ~= b_score then
+            return a_score < b_score and -1 or 1
+        end
+        if a_value and b_value then
+            return compare(a_value, b_value)
+        end
+        return 0
+    end
+
+    local function precedes(rev, comparison, a_member, b_member)
+        if comparison == 0 then
+            if a_member == b_member then
+                return false
+            end
+            comparison = a_member < b_member and -1 or 1
+        end
+        if rev then
+            return comparison > 0
+        end
+        return comparison < 0
+    end
+
+    local function hmget(exact, members)
+        local values = {}
+        for i = 1, #members, 1000 do
+            local chunk = redis.call("HMGET", exact, unpack(members, i, math.min(i + 999, #members)))
+            for _, value in ipairs(chunk) do
+                table.insert(values, value)
+            end
+        end
+        return values
+    end
+
+    local function add_to_sum(sum, previous, score)
+        if sum and redis.call("EXISTS", sum) == 1 then
+            local delta = tonumber(score) - (previous and tonumber(previous) or 0)
+            redis.call("INCRBYFLOAT", sum, string.format("%.17g", delta))
+        end
+    end
+
+    local function insert(scores, exact, sum, mode, score, value, member)
+        local previous = redis.call("ZSCORE", scores, member)
+
+        if value == "" then
+            local changed = redis.call("ZADD", scores, mode, "CH", score, member)
+            if changed == 1 then
+                add_to_sum(sum, previous, score)
+            end
+            return changed
+        end
+
+        local current = redis.call("HGET", exact, member)
+        local write
+        if mode == "NX" then
+            write = not current
+        elseif mode == "XX" then
+            write = current ~= false
+        elseif not current then
+            write = true
+        elseif mode == "GT" then
+            write = compare(value, current) > 0
+        else
+            write = compare(value, current) < 0
+        end
+
+        if not write then
+            return 0
+        end
+        redis.call("ZADD", scores, score, member)
+        redis.call("HSET", exact, member, value)
+        add_to_sum(sum, previous, score)
+        return 1
+    end
+
+    local function count_better(scores, rev, score)
+        if rev then
+            return redis.call("ZCOUNT", scores, "(" .. score, "+inf")
+        end
+        return redis.call("ZCOUNT", scores, "-inf", "(" .. score)
+    end
+
+    local function rank(scores, exact, rev, score, value, member)
+        local current = redis.call("ZSCORE", scores, member)
+        local present = current and tonumber(current) == tonumber(score)
+
+        if not exact or math.abs(tonumber(score)) < INEXACT then
+            if present then
+                return redis.call(rev and "ZREVRANK" or "ZRANK", scores, member)
+            end
+            return count_better(scores, rev, score)
+        end
+
+        local count = count_better(scores, rev, score)
+        local ties = redis.call("ZRANGEBYSCORE", scores, score, score)
+        local values = hmget(exact, ties)
+        for i, tie in ipairs(ties) do
+            local comparison = compare_scores(score, values[i], score, value)
+            if comparison ~= 0 or present then
+                if precedes(rev, comparison, tie, member) then
+                    count = count + 1
+                end
+            end
+        end
+        return count
+    end
+
+    local function range(scores, exact, rev, start, stop)
+        local command = rev and "ZREVRANGE" or "ZRANGE"
+        local entries = redis.call(command, scores, start, stop, "WITHSCORES")
+        if not exact or #entries == 0 then
+            return entries
+        end
+
+        local first, last = entries[2], entries[#entries]
+        local first_inexact = math.abs(tonumber(first)) >= INEXACT
+        local last_inexact = math.abs(tonumber(last)) >= INEXACT
+        if not first_inexact and not last_inexact then
+            return entries
+        end
+
+        local from, to = start, start + #entries / 2 - 1
+        if first_inexact then
+            from = count_better(scores, rev, first)
+        end
+        if last_inexact then
+            if rev then
+                to = redis.call("ZCOUNT", scores, last, "+inf") - 1
+            else
+                to = redis.call("ZCOUNT", scores, "-inf", last) - 1
+            end
+        end
+        if from ~= start or to ~= start + #entries / 2 - 1 then
+            entries = redis.call(command, scores, from, to, "WITHSCORES")
+        end
+
+        local members = {}
+        for i = 1, #entries, 2 do
+            table.insert(members, entries[i])
+        end
+        local values = hmget(exact, members)
+        local items = {}
+        for i, member in ipairs(members) do
+            items[i] = {member, entries[i * 2], values[i]}
+        end
+        table.sort(items, function(a, b)
+            return precedes(rev, compare_scores(a[2], a[3], b[2], b[3]), a[1], b[1])
+        end)
+
+        local result = {}
+        for i = start - from + 1, math.min(stop - from + 1, #items) do
+            table.insert(result, items[i][1])
+            table.insert(result, items[i][2])
+        end
+        return result
+    end
+"#;
+
+
+lazy_static::lazy_static! {
+    /// Script writing a score to sorted sets, comparing it exactly with the current one if it is an integer.
+    ///
+    /// - `KEYS`: triples of the sorted set of the scores, the hash of their exact values and their running sum, one for each sorted set to write the score to
+    /// - `ARGV[1]`: the [`ZADD`](https://redis.io/commands/zadd/) mode to emulate, one of `GT`, `LT`, `NX` or `XX`
+    /// - `ARGV[2]`: the exact score, as a decimal string, or an empty string if the score is not an integer
+    /// - `ARGV[3]`: the approximated score
+    /// - `ARGV[4]`: the player to write the score of
+    ///
+    /// Returns `1` if the score was written to the first sorted set, or `0` otherwise, like `ZADD CH` would.
+    static ref INSERT_SCRIPT: redis::Script = redis::Script::new(&[EXACT_LUA, r#"
+        local changed = 0
+        for i = #KEYS - 2, 1, -3 do
+            changed = insert(KEYS[i], KEYS[i + 1], KEYS[i + 2], ARGV[1], ARGV[3], ARGV[2], ARGV[4])
+        end
+        return changed
+    "#].concat());
+
+    /// Script getting the rank of a player in a sorted set of scores, breaking ties exactly.
+    ///
+    /// - `KEYS[1]`: the sorted set of the scores
+    /// - `KEYS[2]`: the hash of the exact scores
+    /// - `ARGV[1]`: the [`SortingOrder`] of the board, as stored in Redis
+    /// - `ARGV[2]`: the [`ScoreKind`] of the board, as stored in Redis
+    /// - `ARGV[3]`: the player to get the rank of
+    /// - `ARGV[4]`: the score to rank, if it is not in the sorted set, or an empty string
+    /// - `ARGV[5]`: the exact value of the score to rank, if it is not in the sorted set and is an integer, or an empty string
+    ///
+    /// Returns `nil` if the player has no score in the sorted set and none was given.
+    static ref RANK_SCRIPT: redis::Script = redis::Script::new(&[EXACT_LUA, r#"
+        local exact = ARGV[2] == "Integer" and KEYS[2] or nil
+        local score, value = ARGV[4], ARGV[5]
+        if score == "" then
+            score = redis.call("ZSCORE", KEYS[1], ARGV[3])
+            if not score then
+                return false
+            end
+            value = exact and redis.call("HGET", exact, ARGV[3])
+        end
+        if value == "" then
+            value = false
+        end
+        return rank(KEYS[1], exact, ARGV[1] == "Descending", score, value, ARGV[3])
+    "#].concat());
+}
+
+
+/// Get the key of the running sum of the scores in the sorted set at `scores_key`, used to compute their mean.
+///
+/// It is only kept up to date by `insert` while it exists: writers removing scores in any other way delete it instead, so that it is [rebuilt](crate::routes::stats) when needed.
+///
+/// It also expires after a while, so that the rounding errors of the updates do not accumulate forever.
+pub(crate) fn sum_scores_key(scores_key: &str) -> String {
+    format!("{scores_key}:sum")
+}
+
+
+/// Get the key of the hash containing the exact values of the scores in the sorted set at `scores_key`.
+pub(crate) fn exact_scores_key(scores_key: &str) -> String {
+    format!("{scores_key}:exact")
+}
+
+
+/// Write `score` for `player` in the sorted sets at `scores_keys` in a single atomic step, emulating the [`ZADD`](https://redis.io/commands/zadd/) `mode`.
+///
+/// Integer scores are written along with their exact value and compared exactly; returns whether the score changed in the first sorted set.
+pub(crate) async fn insert_score(rconn: &mut redis::aio::Connection, scores_keys: &[String], mode: &str, score: ScoreNumber, player: &str) -> Result<bool, redis::RedisError> {
+    let mut invocation = INSERT_SCRIPT.prepare_invoke();
+    for scores_key in scores_keys.iter() {
+        invocation.key(scores_key).key(exact_scores_key(scores_key)).key(sum_scores_key(scores_key));
+    }
+    invocation
+        .arg(mode)
+        .arg(match score {
+            ScoreNumber::Integer(exact) => exact.to_string(),
+            ScoreNumber::Float(_) => String::new(),
+        })
+        .arg(score.as_f64())
+        .arg(player)
+        .invoke_async::<redis::aio::Connection, bool>(rconn).await
+}
+
+
+/// Get the rank of `player` in the sorted set at `scores_key`, if they have a score in it.
+///
+/// Ties between the approximations of different integers are broken by their exact values.
+pub(crate) async fn get_rank(rconn: &mut redis::aio::Connection, scores_key: &str, kind: ScoreKind, order: SortingOrder, player: &str) -> Result<Option<usize>, redis::RedisError> {
+    log::trace!("Getting the rank of {player:?}...");
+    RANK_SCRIPT
+        .key(scores_key)
+        .key(exact_scores_key(scores_key))
+        .arg(Into::<&str>::into(order))
+        .arg(Into::<&str>::into(kind))
+        .arg(player)
+        .arg("")
+        .arg("")
+        .invoke_async::<redis::aio::Connection, Option<usize>>(rconn).await
+}
+
+
+/// Get the rank `score` would have in the sorted set at `scores_key`, if `player` had it there.
+///
+/// Ties between the approximations of different integers are broken by their exact values.
+pub(crate) async fn get_rank_of(rconn: &mut redis::aio::Connection, scores_key: &str, kind: ScoreKind, order: SortingOrder, player: &str, score: ScoreNumber) -> Result<usize, redis::RedisError> {
+    log::trace!("Getting the rank {score:?} would have...");
+    RANK_SCRIPT
+        .key(scores_key)
+        .key(exact_scores_key(scores_key))
+        .arg(Into::<&str>::into(order))
+        .arg(Into::<&str>::into(kind))
+        .arg(player)
+        .arg(score.as_f64())
+        .arg(match score {
+            ScoreNumber::Integer(exact) => exact.to_string(),
+            ScoreNumber::Float(_) => String::new(),
+        })
+        .invoke_async::<redis::aio::Connection, usize>(rconn).await
+}
+
+
+/// Pair the given `(name, score)` tuples of the sorted set at `scores_key` with the exact values of the scores, if the board has [`ScoreKind::Integer`] scores.
+///
+/// Scores without an exact value are returned as they are sorted.
+pub(crate) async fn get_exact_scores(rconn: &mut redis::aio::Connection, scores_key: &str, kind: ScoreKind, scores: Vec<(String, f64)>) -> Result<Vec<(String, ScoreNumber)>, redis::RedisError> {
+    if kind == ScoreKind::Float || scores.is_empty() {
+        return Ok(scores.into_iter().map(|(name, score)| (name, ScoreNumber::Float(score))).collect())
+    }
+
+    log::trace!("Retrieving the exact values of {} scores...", scores.len());
+    let players: Vec<&str> = scores.iter().map(|(name, _)| name.as_str()).collect();
+    let exact = redis::cmd("HMGET").arg(exact_scores_key(scores_key)).arg(&players)
+        .query_async::<redis::aio::Connection, Vec<Option<String>>>(rconn).await?;
+
+    Ok(
+        scores.into_iter()
+            .zip(exact)
+            .map(|((name, score), exact)| {
+                let score = match exact.and_then(|exact| exact.parse::<i64>().ok()) {
+                    Some(exact) => ScoreNumber::Integer(exact),
+                    None => ScoreNumber::Float(score),
+                };
+                (name, score)
+            })
+            .collect()
+    )
+}
+
+
+/// Get the exact value of the `score` of `player` in the sorted set at `scores_key`, if the board has [`ScoreKind::Integer`] scores.
+pub(crate) async fn get_exact_score(rconn: &mut redis::aio::Connection, scores_key: &str, kind: ScoreKind, player: &str, score: f64) -> Result<ScoreNumber, redis::RedisError> {
+    if kind == ScoreKind::Float {
+        return Ok(ScoreNumber::Float(score))
+    }
+
+    log::trace!("Retrieving the exact value of the score of {player:?}...");
+    let exact = rconn.hget::<String, &str, Option<String>>(exact_scores_key(scores_key), player).await?;
+
+    Ok(match exact.and_then(|exact| exact.parse::<i64>().ok()) {
+        Some(exact) => ScoreNumber::Integer(exact),
+        None => ScoreNumber::Float(score),
+    })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::EXACT_LUA;
+
+    /// Call the Lua function `name` defined in [`EXACT_LUA`] with the given arguments.
+    fn call<'a, R: mlua::FromLuaMulti<'a>>(lua: &'a mlua::Lua, name: &str, args: impl mlua::IntoLuaMulti<'a>) -> R {
+        let function: mlua::Function = lua.load([EXACT_LUA, "return ", name].concat()).eval()
+            .expect("Lua functions to be valid");
+        function.call(args)
+            .expect("Lua function to succeed")
+    }
+
+    #[test]
+    fn compare_equal() {
+        let lua = mlua::Lua::new();
+        assert_eq!(call::<i64>(&lua, "compare", ("0", "0")), 0);
+        assert_eq!(call::<i64>(&lua, "compare", ("9223372036854775807", "9223372036854775807")), 0);
+        assert_eq!(call::<i64>(&lua, "compare", ("-9223372036854775808", "-9223372036854775808")), 0);
+    }
+
+    #[test]
+    fn compare_same_length() {
+        let lua = mlua::Lua::new();
+        assert_eq!(call::<i64>(&lua, "compare", ("9007199254740993", "9007199254740992")), 1);
+        assert_eq!(call::<i64>(&lua, "compare", ("9007199254740992", "9007199254740993")), -1);
+    }
+
+    #[test]
+    fn compare_different_lengths() {
+        let lua = mlua::Lua::new();
+        assert_eq!(call::<i64>(&lua, "compare", ("10", "9")), 1);
+        assert_eq!(call::<i64>(&lua, "compare", ("9", "10")), -1);
+        assert_eq!(call::<i64>(&lua, "compare", ("10000000000000000001", "999")), 1);
+    }
+
+    #[test]
+    fn compare_negatives() {
+        let lua = mlua::Lua::new();
+        assert_eq!(call::<i64>(&lua, "compare", ("-1", "1")), -1);
+        assert_eq!(call::<i64>(&lua, "compare", ("1", "-1")), 1);
+        assert_eq!(call::<i64>(&lua, "compare", ("-1", "0")), -1);
+        assert_eq!(call::<i64>(&lua, "compare", ("-10", "-9")), -1);
+        assert_eq!(call::<i64>(&lua, "compare", ("-9", "-10")), 1);
+        assert_eq!(call::<i64>(&lua, "compare", ("-9007199254740993", "-9007199254740992")), -1);
+    }
+
+    #[test]
+    fn compare_scores_breaks_ties_exactly() {
+        let lua = mlua::Lua::new();
+        assert_eq!(call::<i64>(&lua, "compare_scores", ("1", "1", "2", "2")), -1);
+        assert_eq!(call::<i64>(&lua, "compare_scores", ("9007199254740992", "9007199254740993", "9007199254740992", "9007199254740992")), 1);
+        assert_eq!(call::<i64>(&lua, "compare_scores", ("9007199254740992", "9007199254740992", "9007199254740992", "9007199254740992")), 0);
+        assert_eq!(call::<i64>(&lua, "compare_scores", ("9007199254740992", mlua::Value::Nil, "9007199254740992", "9007199254740993")), 0);
+    }
+}
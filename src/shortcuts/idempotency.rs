@@ -0,0 +1,137 @@
+//! Module defining how requests carrying an `Idempotency-Key` header are deduplicated.
+
+use axum::http::{HeaderMap, StatusCode};
+use axum::extract::Json;
+use redis::AsyncCommands;
+use serde::Serialize;
+use serde::Deserialize;
+use serde_json::Value;
+use crate::outcome;
+use crate::shortcuts::token::TOKEN_ID_SECRET;
+use crate::utils::token::token_id;
+use crate::config;
+
+
+/// The name of the header containing the idempotency key.
+pub(crate) const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+
+/// For how many seconds a key stays reserved while its request is being processed, so that requests interrupted midway do not lock it for the whole [`IDEMPOTENCY_TTL_SECONDS`](config::IDEMPOTENCY_TTL_SECONDS).
+const PENDING_TTL_SECONDS: usize = 60;
+
+
+/// A request being deduplicated, as stored in [Redis].
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct IdempotentRequest {
+    /// A representation of the request, to detect keys being reused for different requests.
+    fingerprint: String,
+    /// The status code of the response, or [`None`] if the request is still being processed.
+    status: Option<u16>,
+    /// The body of the response, or [`None`] if the request is still being processed.
+    body: Option<Value>,
+}
+
+
+/// An idempotency key sent by a client, scoped to the board and the token it was sent with.
+#[derive(Clone, Debug)]
+pub(crate) struct IdempotencyKey {
+    /// The key of the [`IdempotentRequest`] in Redis.
+    key: String,
+    /// A representation of the request.
+    fingerprint: String,
+}
+
+impl IdempotencyKey {
+    /// Get the [`IdempotencyKey`] of a request to `board` authorized by `token` from its headers, if it has one.
+    ///
+    /// `fingerprint` should change whenever the request would have a different effect.
+    pub(crate) fn from_headers_or_400(headers: &HeaderMap, board: &str, token: &str, fingerprint: &impl Serialize) -> Result<Option<Self>, outcome::RequestTuple> {
+        log::trace!("Searching for the Idempotency-Key header...");
+        let key = match headers.get(IDEMPOTENCY_KEY_HEADER) {
+            Some(key) => key,
+            None => return Ok(None),
+        };
+
+        let key = key.to_str()
+            .map_err(|_| (StatusCode::BAD_REQUEST, outcome::req_error!("Malformed Idempotency-Key header")))?;
+        if key.is_empty() || key.len() > 255 {
+            return Err((StatusCode::BAD_REQUEST, outcome::req_error!("Idempotency-Key header must be between 1 and 255 characters long")))
+        }
+
+        let fingerprint = serde_json::to_string(fingerprint).expect("fingerprint to be serializable");
+
+        Ok(Some(Self {
+            key: format!("board:{board}:idempotency:{}:{}", token_id(&TOKEN_ID_SECRET, token), token_id(&TOKEN_ID_SECRET, key)),
+            fingerprint,
+        }))
+    }
+
+    /// Reserve the key for the current request, or return the response to replay if it was already used.
+    ///
+    /// Fails with `409 Conflict` if the key was used for a different request, or if the first request with the key is still being processed.
+    pub(crate) async fn reserve_or_replay(&self, rconn: &mut redis::aio::Connection) -> Result<Option<outcome::RequestTuple>, outcome::RequestTuple> {
+        let pending = IdempotentRequest {fingerprint: self.fingerprint.clone(), status: None, body: None};
+        let pending = serde_json::to_string(&pending).expect("request to be serializable");
+
+        // The key may expire between the two commands, in which case it is as if it was never used.
+        let stored = loop {
+            log::trace!("Reserving the idempotency key...");
+            let reserved = redis::cmd("SET").arg(&self.key).arg(&pending)
+                .arg("NX").arg("EX").arg(PENDING_TTL_SECONDS)
+                .query_async::<redis::aio::Connection, Option<String>>(rconn).await
+                .map_err(outcome::redis_cmd_failed)?
+                .is_some();
+            if reserved {
+                return Ok(None)
+            }
+
+            log::trace!("Idempotency key was already used, retrieving the stored response...");
+            let stored = rconn.get::<&str, Option<String>>(&self.key).await
+                .map_err(outcome::redis_cmd_failed)?
+                .map(|stored| serde_json::from_str::<IdempotentRequest>(&stored))
+                .transpose()
+                .map_err(|_| outcome::redis_unexpected_behaviour())?;
+
+            if let Some(stored) = stored {
+                break stored
+            }
+        };
+
+        if stored.fingerprint != self.fingerprint {
+            return Err((StatusCode::CONFLICT, outcome::req_error!("Idempotency key was already used for a different request")))
+        }
+
+        match (stored.status, stored.body) {
+            (Some(status), Some(body)) => {
+                let status = StatusCode::from_u16(status)
+                    .map_err(|_| outcome::redis_unexpected_behaviour())?;
+                log::trace!("Replaying stored response...");
+                Ok(Some((status, Json(body))))
+            },
+            _ => Err((StatusCode::CONFLICT, outcome::req_error!("A request with the same idempotency key is still being processed"))),
+        }
+    }
+
+    /// Store the response to the current request, so that it can be replayed, keeping the key for the whole [`IDEMPOTENCY_TTL_SECONDS`](config::IDEMPOTENCY_TTL_SECONDS).
+    ///
+    /// Errors are stored as well, since the request might have had an effect before failing: use [`release`](Self::release) for requests refused before doing anything.
+    pub(crate) async fn complete(&self, rconn: &mut redis::aio::Connection, result: &outcome::RequestResult) -> Result<(), redis::RedisError> {
+        let (status, Json(body)) = match result {
+            Ok(response) => response,
+            Err(response) => response,
+        };
+
+        log::trace!("Storing the response for the idempotency key...");
+        let completed = IdempotentRequest {
+            fingerprint: self.fingerprint.clone(),
+            status: Some(status.as_u16()),
+            body: Some(body.clone()),
+        };
+        rconn.set_ex::<&str, String, ()>(&self.key, serde_json::to_string(&completed).expect("request to be serializable"), *config::IDEMPOTENCY_TTL_SECONDS).await
+    }
+
+    /// Release the key, so that the request can be retried, as it was refused before doing anything.
+    pub(crate) async fn release(&self, rconn: &mut redis::aio::Connection) -> Result<(), redis::RedisError> {
+        log::trace!("Releasing the idempotency key...");
+        rconn.del::<&str, ()>(&self.key).await
+    }
+}
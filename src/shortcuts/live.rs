@@ -0,0 +1,138 @@
+//! Module defining how the live subscribers of a board share a single Pub/Sub connection.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use axum::http::StatusCode;
+use futures::StreamExt;
+use tokio::sync::{broadcast, Mutex};
+use crate::outcome;
+use crate::shortcuts::redis::RedisConnectOr504;
+use crate::shortcuts::webhooks::events_channel;
+use crate::config;
+
+
+/// How many events are buffered for each subscriber, before the slowest ones start missing them.
+const LIVE_BUFFER_SIZE: usize = 256;
+
+/// How often the Pub/Sub connection of an idle board checks whether it still has subscribers.
+const LIVE_IDLE_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+
+/// The [`LiveHub`] shared by all the requests handled by this server instance.
+pub(crate) type SharedLiveHub = Arc<LiveHub>;
+
+
+/// The Pub/Sub connections of the boards with live subscribers on this server instance, each shared by all the subscribers of its board.
+pub(crate) struct LiveHub {
+    /// The client to open the Pub/Sub connections with.
+    rclient: redis::Client,
+    /// The senders forwarding the events of each board to its subscribers, by board.
+    channels: Mutex<HashMap<String, broadcast::Sender<String>>>,
+    /// How many subscribers are connected to this server instance, across all boards.
+    subscribers: Arc<AtomicUsize>,
+}
+
+
+/// A place among the [`config::LIVE_MAX_SUBSCRIBERS`], freed when dropped.
+struct SubscriberSlot(Arc<AtomicUsize>);
+
+impl Drop for SubscriberSlot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+
+/// A subscription to the events of a board, as the [`QueuedEvent`](crate::shortcuts::webhooks::QueuedEvent)s published on its [events channel](events_channel), serialized as JSON.
+pub(crate) struct LiveReceiver {
+    /// The events of the board.
+    pub(crate) events: broadcast::Receiver<String>,
+    /// The place of the subscriber, held for as long as the subscription.
+    _slot: SubscriberSlot,
+}
+
+
+impl LiveHub {
+    /// Create a hub without any subscribers.
+    pub(crate) fn new(rclient: redis::Client) -> SharedLiveHub {
+        Arc::new(Self {
+            rclient,
+            channels: Mutex::new(HashMap::new()),
+            subscribers: Arc::new(AtomicUsize::new(0)),
+        })
+    }
+
+    /// Subscribe to the events of `board`, opening its Pub/Sub connection if it has no other subscribers.
+    ///
+    /// Returns `503 Service Unavailable` if there are already [too many subscribers](config::LIVE_MAX_SUBSCRIBERS).
+    pub(crate) async fn subscribe_or_503(self: &Arc<Self>, board: &str) -> Result<LiveReceiver, outcome::RequestTuple> {
+        log::trace!("Ensuring the number of live subscribers is within limits...");
+        self.subscribers.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |count| (count < *config::LIVE_MAX_SUBSCRIBERS).then_some(count + 1))
+            .map_err(|_| (StatusCode::SERVICE_UNAVAILABLE, outcome::req_error!("Too many live subscribers, try again later")))?;
+        let slot = SubscriberSlot(self.subscribers.clone());
+
+        if let Some(sender) = self.channels.lock().await.get(board) {
+            log::trace!("Sharing the Pub/Sub connection of {board:?}...");
+            return Ok(LiveReceiver {events: sender.subscribe(), _slot: slot})
+        }
+
+        // Connecting without holding the lock, so that a slow Redis does not hold up the subscribers of other boards.
+        log::debug!("Opening the Pub/Sub connection of {board:?}...");
+        let mut pubsub = self.rclient.get_connection_or_504().await?.into_pubsub();
+        pubsub.subscribe(events_channel(board)).await
+            .map_err(outcome::redis_cmd_failed)?;
+
+        let mut channels = self.channels.lock().await;
+        if let Some(sender) = channels.get(board) {
+            log::trace!("Another subscriber opened the Pub/Sub connection of {board:?} first, closing this one...");
+            return Ok(LiveReceiver {events: sender.subscribe(), _slot: slot})
+        }
+
+        let (sender, events) = broadcast::channel(LIVE_BUFFER_SIZE);
+        channels.insert(board.to_string(), sender.clone());
+        tokio::spawn(self.clone().forward(board.to_string(), pubsub, sender));
+
+        Ok(LiveReceiver {events, _slot: slot})
+    }
+
+    /// Forward the messages published on the events channel of `board` to its subscribers, closing the connection once none are left.
+    async fn forward(self: Arc<Self>, board: String, pubsub: redis::aio::PubSub, sender: broadcast::Sender<String>) {
+        let mut messages = pubsub.into_on_message().boxed();
+        let mut interval = tokio::time::interval(LIVE_IDLE_CHECK_INTERVAL);
+
+        loop {
+            tokio::select! {
+                message = messages.next() => {
+                    let message = match message {
+                        Some(message) => message,
+                        None => {
+                            log::warn!("Pub/Sub connection of {board:?} was closed");
+                            break
+                        }
+                    };
+                    match message.get_payload::<String>() {
+                        // Sending only fails if there are no subscribers, which is checked below.
+                        Ok(payload) => { let _ = sender.send(payload); },
+                        Err(_) => log::warn!("Discarding malformed message on {:?}", message.get_channel_name()),
+                    }
+                },
+                _ = interval.tick() => {},
+            }
+
+            if sender.receiver_count() == 0 {
+                // Checked again while holding the lock, as a subscriber may have joined in the meantime.
+                let mut channels = self.channels.lock().await;
+                if sender.receiver_count() == 0 {
+                    log::debug!("Closing the Pub/Sub connection of {board:?}...");
+                    channels.remove(&board);
+                    return
+                }
+            }
+        }
+
+        // Dropping the senders ends the subscriptions, so that clients reconnect with a new connection.
+        self.channels.lock().await.remove(&board);
+    }
+}
@@ -1,4 +1,19 @@
 //! Module containing utilities that **are** specific to [`distributed_arcade`].
 
+pub(crate) mod audit;
+pub(crate) mod ban;
+pub(crate) mod board;
+pub(crate) mod names;
+pub(crate) mod page;
 pub(crate) mod redis;
+pub(crate) mod season;
 pub(crate) mod token;
+
+pub(crate) mod webhooks;
+pub(crate) mod submit;
+pub(crate) mod idempotency;
+pub(crate) mod exact;
+pub(crate) mod blobs;
+pub(crate) mod replays;
+pub(crate) mod state;
+pub(crate) mod live;
\ No newline at end of file
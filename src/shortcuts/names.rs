@@ -0,0 +1,49 @@
+/// Get the key of the hash containing the display names of the players of `board`, or of its archived `season`.
+pub(crate) fn names_key(board: &str, season: Option<&str>) -> String {
+    match season {
+        Some(season) => format!("board:{board}:names:season:{season}"),
+        None => format!("board:{board}:names"),
+    }
+}
+
+
+/// Retrieve the display names of the given players of `board`, or of its archived `season`, in the same order.
+///
+/// Players without a stored display name fall back to their normalized name.
+pub(crate) async fn get_display_names(rconn: &mut redis::aio::Connection, board: &str, season: Option<&str>, players: &[&str]) -> Result<Vec<String>, redis::RedisError> {
+    if players.is_empty() {
+        return Ok(vec![])
+    }
+
+    log::trace!("Retrieving the display names of {} players...", players.len());
+    let names = redis::cmd("HMGET").arg(names_key(board, season)).arg(players)
+        .query_async::<redis::aio::Connection, Vec<Option<String>>>(rconn).await?;
+
+    Ok(
+        names.into_iter()
+            .zip(players)
+            .map(|(name, player)| name.unwrap_or_else(|| player.to_string()))
+            .collect()
+    )
+}
+
+
+/// Get the key of the hash containing when the entries of a [`BoardMode::Initials`](crate::utils::mode::BoardMode::Initials) `board`, or of its archived `season`, were submitted.
+pub(crate) fn submitted_key(board: &str, season: Option<&str>) -> String {
+    match season {
+        Some(season) => format!("board:{board}:submitted:season:{season}"),
+        None => format!("board:{board}:submitted"),
+    }
+}
+
+
+/// Retrieve when the given entries of a [`BoardMode::Initials`](crate::utils::mode::BoardMode::Initials) `board`, or of its archived `season`, were submitted, in the same order.
+pub(crate) async fn get_submitted_at(rconn: &mut redis::aio::Connection, board: &str, season: Option<&str>, entries: &[&str]) -> Result<Vec<Option<i64>>, redis::RedisError> {
+    if entries.is_empty() {
+        return Ok(vec![])
+    }
+
+    log::trace!("Retrieving when {} entries were submitted...", entries.len());
+    redis::cmd("HMGET").arg(submitted_key(board, season)).arg(entries)
+        .query_async::<redis::aio::Connection, Vec<Option<i64>>>(rconn).await
+}
@@ -0,0 +1,111 @@
+use crate::shortcuts::exact::{exact_scores_key, EXACT_LUA};
+use crate::utils::cursor::Cursor;
+use crate::utils::kind::ScoreKind;
+use crate::utils::sorting::SortingOrder;
+
+
+lazy_static::lazy_static! {
+    /// Script retrieving a page of scores from a board in a single atomic step.
+    ///
+    /// - `KEYS[1]`: the scores key of the board
+    /// - `KEYS[2]`: the key of the exact values of the scores of the board
+    /// - `ARGV[1]`: the [`SortingOrder`] of the board, as stored in Redis
+    /// - `ARGV[2]`: the [`ScoreKind`] of the board, as stored in Redis
+    /// - `ARGV[3]`: how many scores to return
+    /// - `ARGV[4]`: either `offset` or `cursor`
+    /// - `ARGV[5]`: the offset to start from, or the score of the cursor
+    /// - `ARGV[6]`: the member of the cursor
+    ///
+    /// The cursor is located by the rank of its member; only if the member was removed or changed score since, the ties of its score are scanned instead.
+    ///
+    /// Returns the rank of the first returned score, the total number of scores in the board, and the scores.
+    static ref PAGE_SCRIPT: redis::Script = redis::Script::new(&[EXACT_LUA, r#"
+        local rev = ARGV[1] == "Descending"
+        local exact = ARGV[2] == "Integer" and KEYS[2] or nil
+        local size = tonumber(ARGV[3])
+        local start = 0
+        if ARGV[4] == "offset" then
+            start = tonumber(ARGV[5])
+        else
+            local score = ARGV[5]
+            local member = ARGV[6]
+            local current = redis.call("ZSCORE", KEYS[1], member)
+            if current and tonumber(current) == tonumber(score) then
+                start = rank(KEYS[1], exact, rev, current, exact and redis.call("HGET", exact, member), member) + 1
+            else
+                start = count_better(KEYS[1], rev, score)
+                for _, tie in ipairs(redis.call("ZRANGEBYSCORE", KEYS[1], score, score)) do
+                    if (rev and tie >= member) or (not rev and tie <= member) then
+                        start = start + 1
+                    end
+                end
+            end
+        end
+        local total = redis.call("ZCARD", KEYS[1])
+        if size == 0 then
+            return {start, total, {}}
+        end
+        return {start, total, range(KEYS[1], exact, rev, start, start + size - 1)}
+    "#].concat());
+}
+
+
+/// Where a page of scores should start from.
+#[derive(Clone, Debug)]
+pub(crate) enum PageStart {
+    /// Start from the given zero-based rank.
+    Offset(usize),
+    /// Start from the entry immediately after the given [`Cursor`].
+    After(Cursor),
+}
+
+
+/// A page of scores retrieved with [`get_page`].
+#[derive(Clone, Debug)]
+pub(crate) struct Page {
+    /// The rank of the first score of the page, zero-based.
+    pub start: usize,
+    /// The total number of scores in the board.
+    pub total: usize,
+    /// The scores in the page, in the order of the board.
+    pub scores: Vec<(String, f64)>,
+}
+
+impl Page {
+    /// Get the [`Cursor`] pointing to the next page, or [`None`] if this is the last one.
+    pub fn next_cursor(&self) -> Option<Cursor> {
+        if self.start + self.scores.len() >= self.total {
+            return None
+        }
+
+        self.scores.last().map(|(name, score)| Cursor {
+            score: *score,
+            name: name.clone(),
+        })
+    }
+}
+
+
+/// Retrieve a page of `size` scores from the board stored at `scores_key`.
+///
+/// On [`ScoreKind::Integer`] boards, ties between the approximations of different integers are broken by their exact values.
+pub(crate) async fn get_page(rconn: &mut redis::aio::Connection, scores_key: &str, kind: ScoreKind, order: SortingOrder, start: &PageStart, size: usize) -> Result<Page, redis::RedisError> {
+    log::trace!("Retrieving page of {size} scores starting from {start:?}...");
+
+    let mut invocation = PAGE_SCRIPT.key(scores_key);
+    invocation
+        .key(exact_scores_key(scores_key))
+        .arg(Into::<&str>::into(order))
+        .arg(Into::<&str>::into(kind))
+        .arg(size);
+    match start {
+        PageStart::Offset(offset) => invocation.arg("offset").arg(offset),
+        PageStart::After(cursor) => invocation.arg("cursor").arg(cursor.score).arg(&cursor.name),
+    };
+
+    let (start, total, scores) = invocation
+        .invoke_async::<redis::aio::Connection, (usize, usize, Vec<(String, f64)>)>(rconn).await?;
+
+    log::trace!("Retrieved {} scores out of {total}, starting from rank {start}", scores.len());
+    Ok(Page {start, total, scores})
+}
@@ -0,0 +1,70 @@
+//! Module defining the metadata and replays attached to score entries.
+//!
+//! Both are only kept for the entry that currently counts: they are discarded whenever the entry is replaced by a better score, or removed.
+
+use serde::Serialize;
+use serde::Deserialize;
+use crate::shortcuts::blobs::BlobStore;
+use crate::utils::kind::ScoreNumber;
+
+
+/// Get the key of the hash containing the metadata of the entries of `board`, or of its archived `season`.
+pub(crate) fn metadata_key(board: &str, season: Option<&str>) -> String {
+    match season {
+        Some(season) => format!("board:{board}:metadata:season:{season}"),
+        None => format!("board:{board}:metadata"),
+    }
+}
+
+
+/// Get the key of the hash containing the [`ReplayObject`]s of the entries of `board`, or of its archived `season`.
+pub(crate) fn replays_key(board: &str, season: Option<&str>) -> String {
+    match season {
+        Some(season) => format!("board:{board}:replays:season:{season}"),
+        None => format!("board:{board}:replays"),
+    }
+}
+
+
+/// A replay or proof attached to an entry, as stored in [Redis].
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(crate) struct ReplayObject {
+    /// The id of the replay in the [`BlobStore`].
+    pub(crate) id: String,
+    /// The score the entry had when the replay was uploaded, exactly if it is an integer.
+    pub(crate) score: ScoreNumber,
+    /// The size of the replay, in bytes.
+    pub(crate) size: usize,
+    /// The UNIX timestamp at which the replay was uploaded.
+    pub(crate) uploaded_at: i64,
+}
+
+
+/// Queue the commands detaching the replay of `player` from its entry in the given pipeline.
+///
+/// Only the first command returns a value: the detached [`ReplayObject`], if any, whose blob should then be deleted with [`delete_replays`].
+pub(crate) fn detach_replay_to(pipe: &mut redis::Pipeline, replays_key: &str, player: &str) {
+    pipe.hget(replays_key, player);
+    pipe.hdel(replays_key, player).ignore();
+}
+
+
+/// Delete the blobs of the given detached [`ReplayObject`]s from the [`BlobStore`].
+///
+/// Failures are only logged, as the replays are not reachable anymore.
+pub(crate) async fn delete_replays(blobs: &dyn BlobStore, detached: Vec<Option<String>>) {
+    for replay in detached.into_iter().flatten() {
+        let replay = match serde_json::from_str::<ReplayObject>(&replay) {
+            Ok(replay) => replay,
+            Err(_) => {
+                log::warn!("Discarding malformed replay: {replay:?}");
+                continue
+            }
+        };
+
+        log::trace!("Deleting detached replay {:?}...", replay.id);
+        if let Err(err) = blobs.delete(&replay.id).await {
+            log::error!("{err:#?}");
+        }
+    }
+}
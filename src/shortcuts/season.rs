@@ -0,0 +1,138 @@
+use axum::http::StatusCode;
+use redis::AsyncCommands;
+use crate::outcome;
+use crate::shortcuts::ban::shadow_scores_key;
+use crate::shortcuts::exact::{exact_scores_key, sum_scores_key};
+use crate::shortcuts::names::{names_key, submitted_key};
+use crate::shortcuts::replays::{metadata_key, replays_key};
+use crate::shortcuts::webhooks::{emit_to, BoardEvent};
+use crate::utils::period::Period;
+use crate::config;
+
+
+lazy_static::lazy_static! {
+    /// Script moving the live scores of a board into a season archive in a single atomic step.
+    ///
+    /// - `KEYS[1]`: the seasons key of the board
+    /// - `KEYS[2]`: the key of the scheduled season archivals
+    /// - `KEYS[3]`: the running sum of the live scores, which is deleted
+    /// - the following `KEYS`, in pairs: a key of the live board to archive, if it exists, and the key to move it to
+    /// - `ARGV[1]`: the name of the season to archive
+    /// - `ARGV[2]`: the UNIX timestamp of the archival
+    /// - `ARGV[3]`: the scheduled archival to claim, or an empty string if the archival was not scheduled
+    ///
+    /// Returns `1` if the season was archived, `0` if a season with the same name already exists, or `-1` if the scheduled archival was already claimed.
+    static ref ARCHIVE_SCRIPT: redis::Script = redis::Script::new(r#"
+        if ARGV[3] ~= "" and redis.call("ZREM", KEYS[2], ARGV[3]) == 0 then
+            return -1
+        end
+        if redis.call("ZSCORE", KEYS[1], ARGV[1]) then
+            return 0
+        end
+        redis.call("DEL", KEYS[3])
+        for i = 4, #KEYS - 1, 2 do
+            if redis.call("EXISTS", KEYS[i]) == 1 then
+                redis.call("RENAME", KEYS[i], KEYS[i + 1])
+            end
+        end
+        redis.call("ZADD", KEYS[1], ARGV[2], ARGV[1])
+        return 1
+    "#);
+}
+
+
+/// Key of the sorted set containing the season archivals scheduled on all boards, by UNIX timestamp.
+pub(crate) const SCHEDULED_SEASONS_KEY: &str = "seasons:scheduled";
+
+
+/// Archive the live scores of `board` as `season`, leaving the live board empty.
+///
+/// Returns `false` if a season with the same name was already archived.
+///
+/// Webhooks subscribed to [`BoardEvent::BoardReset`] and live subscribers are notified of successful archivals.
+pub(crate) async fn archive_season(rconn: &mut redis::aio::Connection, board: &str, season: &str) -> Result<bool, redis::RedisError> {
+    let archived = archive(rconn, board, season, None).await?;
+    Ok(archived.unwrap_or(false))
+}
+
+
+/// Archive the live scores of `board` as `season`, claiming the `scheduled` archival in the same atomic step, so that only one server instance archives it.
+///
+/// Returns [`None`] if the scheduled archival was already claimed by another instance, or `false` if a season with the same name was already archived.
+pub(crate) async fn archive_scheduled_season(rconn: &mut redis::aio::Connection, scheduled: &str, board: &str, season: &str) -> Result<Option<bool>, redis::RedisError> {
+    archive(rconn, board, season, Some(scheduled)).await
+}
+
+
+/// Archive the live scores of `board` as `season`, along with everything attached to them, claiming the `scheduled` archival if given.
+///
+/// Hidden scores and the leaderboards of the current periods are archived too, so that they restart with the new season.
+async fn archive(rconn: &mut redis::aio::Connection, board: &str, season: &str, scheduled: Option<&str>) -> Result<Option<bool>, redis::RedisError> {
+    log::debug!("Archiving season {season:?} of {board:?}...");
+
+    log::trace!("Determining the current periods...");
+    let periods = rconn.smembers::<String, Vec<String>>(format!("board:{board}:periods")).await?;
+    let now = chrono::Utc::now().with_timezone(&*config::PERIODS_TIMEZONE);
+    let period_keys: Vec<String> = periods.iter()
+        .filter_map(|period| Period::try_from(period.as_str()).ok())
+        .map(|period| format!("board:{board}:scores:{}", period.id_at(&now)))
+        .collect();
+
+    let mut sets = vec![
+        format!("board:{board}:scores"),
+        shadow_scores_key(board),
+    ];
+    sets.extend(period_keys);
+
+    let mut invocation = ARCHIVE_SCRIPT.prepare_invoke();
+    invocation
+        .key(format!("board:{board}:seasons"))
+        .key(SCHEDULED_SEASONS_KEY)
+        .key(sum_scores_key(&format!("board:{board}:scores")));
+    for set in sets.iter() {
+        let archived_set = format!("{set}:season:{season}");
+        invocation
+            .key(set)
+            .key(&archived_set)
+            .key(exact_scores_key(set))
+            .key(exact_scores_key(&archived_set));
+    }
+    invocation
+        .key(names_key(board, None))
+        .key(names_key(board, Some(season)))
+        .key(submitted_key(board, None))
+        .key(submitted_key(board, Some(season)))
+        .key(metadata_key(board, None))
+        .key(metadata_key(board, Some(season)))
+        .key(replays_key(board, None))
+        .key(replays_key(board, Some(season)))
+        .arg(season)
+        .arg(chrono::Utc::now().timestamp())
+        .arg(scheduled.unwrap_or(""));
+
+    let archived = match invocation.invoke_async::<redis::aio::Connection, i64>(rconn).await? {
+        -1 => return Ok(None),
+        archived => archived > 0,
+    };
+
+    if archived {
+        log::trace!("Emitting event for webhooks and live subscribers...");
+        let mut pipe = redis::pipe();
+        emit_to(&mut pipe, board, BoardEvent::BoardReset {season: season.to_string()});
+        pipe.query_async::<redis::aio::Connection, ()>(rconn).await?;
+    }
+
+    Ok(Some(archived))
+}
+
+
+/// Get the scores key of the archived `season` of `board`, ensuring that it exists.
+pub(crate) async fn archived_season_scores_key(rconn: &mut redis::aio::Connection, board: &str, season: &str) -> Result<String, outcome::RequestTuple> {
+    log::trace!("Ensuring the season {season:?} of {board:?} is archived...");
+
+    rconn.zscore::<String, &str, Option<i64>>(format!("board:{board}:seasons"), season).await
+        .map_err(outcome::redis_cmd_failed)?
+        .ok_or_else(|| (StatusCode::NOT_FOUND, outcome::req_error!("No such season")))?;
+
+    Ok(format!("board:{board}:scores:season:{season}"))
+}
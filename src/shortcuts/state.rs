@@ -0,0 +1,54 @@
+//! Module defining whether boards are accepting submissions.
+
+use axum::http::StatusCode;
+use serde::Serialize;
+use serde::Deserialize;
+use crate::outcome;
+
+
+/// Whether a board is accepting submissions, as a serializable struct.
+///
+/// Also used as the expected body for [`PUT /board/state/`](crate::routes::state::route_board_state_put).
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(crate) struct BoardState {
+    /// Whether the board has been manually locked.
+    #[serde(default)]
+    pub(crate) locked: bool,
+    /// The UNIX timestamp before which submissions are not accepted, if any.
+    pub(crate) opens_at: Option<i64>,
+    /// The UNIX timestamp from which submissions are not accepted anymore, if any.
+    pub(crate) closes_at: Option<i64>,
+}
+
+impl BoardState {
+    /// Check whether the board accepts submissions at the given UNIX timestamp.
+    pub(crate) fn is_open_at(&self, now: i64) -> bool {
+        !self.locked
+            && self.opens_at.filter(|opens_at| now < *opens_at).is_none()
+            && self.closes_at.filter(|closes_at| now >= *closes_at).is_none()
+    }
+
+    /// Ensure that the board accepted submissions at the given UNIX timestamp, returning `423 Locked` otherwise.
+    pub(crate) fn ensure_open_at_or_423(&self, now: i64) -> Result<(), outcome::RequestTuple> {
+        log::trace!("Ensuring the board is accepting submissions...");
+        match self.is_open_at(now) {
+            true => Ok(()),
+            false => Err((StatusCode::LOCKED, outcome::req_error!("Board is not accepting submissions"))),
+        }
+    }
+}
+
+
+/// Retrieve the [`BoardState`] of the given board.
+pub(crate) async fn get_board_state(rconn: &mut redis::aio::Connection, board: &str) -> Result<BoardState, outcome::RequestTuple> {
+    log::trace!("Retrieving the state of {board:?}...");
+    let (locked, opens_at, closes_at) = redis::cmd("HMGET").arg(format!("board:{board}:state")).arg("locked").arg("opens_at").arg("closes_at")
+        .query_async::<redis::aio::Connection, (Option<bool>, Option<i64>, Option<i64>)>(rconn).await
+        .map_err(outcome::redis_cmd_failed)?;
+
+    Ok(BoardState {
+        locked: locked.unwrap_or(false),
+        opens_at,
+        closes_at,
+    })
+}
@@ -0,0 +1,492 @@
+//! Module defining how scores are submitted to boards, shared by single, batch and synchronized submissions.
+
+use axum::http::StatusCode;
+use chrono::TimeZone;
+use redis::AsyncCommands;
+use crate::outcome;
+use crate::routes::score::{PeriodRankObject, RouteScoreResponse};
+use crate::shortcuts::audit::{audit_to, Origin};
+use crate::shortcuts::ban::{get_ban_kind, hide_score, restore_score, shadow_scores_key, BanKind};
+use crate::shortcuts::blobs::BlobStore;
+use crate::shortcuts::board::{get_board_mode, get_score_kind, get_score_schema};
+use crate::shortcuts::exact::{exact_scores_key, sum_scores_key, EXACT_LUA};
+use crate::shortcuts::names::{names_key, submitted_key};
+use crate::shortcuts::replays::{delete_replays, metadata_key, replays_key};
+use crate::shortcuts::state::{get_board_state, BoardState};
+use crate::shortcuts::token::Generate;
+use crate::shortcuts::webhooks::{emit_to, BoardEvent};
+use crate::utils::kebab::Skewer;
+use crate::utils::kind::{ScoreKind, ScoreNumber};
+use crate::utils::mode::BoardMode;
+use crate::utils::period::Period;
+use crate::utils::schema::{ScoreSchema, ScoreValue};
+use crate::utils::sorting::SortingOrder;
+use crate::utils::token::SecureToken;
+use crate::config;
+
+
+lazy_static::lazy_static! {
+    /// Script writing submissions in a single atomic step, replacing the metadata and detaching the replay of every entry whose score changed.
+    ///
+    /// - `ARGV[1]`: the number of submissions
+    ///
+    /// Then, for each submission:
+    ///
+    /// - nine `KEYS`: the sorted set to write the score to, the hash of its exact values and its running sum, the sorted set to rank the score in and the hash of its exact values, then the display names, the metadata, the replays and the submission times of the entries of the board
+    /// - two more `KEYS` for each period leaderboard the score belongs to: its sorted set and the hash of its exact values
+    /// - ten `ARGV`: the entry, its display name, the [`ZADD`](https://redis.io/commands/zadd/) mode, the score, its exact value or an empty string, its metadata or an empty string, when it was submitted or an empty string, the [`SortingOrder`] of the board, whether the score is hidden, and the number of period leaderboards
+    /// - one more `ARGV` for each period leaderboard: the UNIX timestamp it expires at
+    ///
+    /// Hidden scores are not written to the period leaderboards, but are ranked on them as if they were.
+    ///
+    /// Returns, for each submission, whether the score changed, the current score and its exact value, its rank, the detached replay, if any, and its rank on each period leaderboard.
+    static ref SUBMIT_SCRIPT: redis::Script = redis::Script::new(&[EXACT_LUA, r#"
+        local results = {}
+        local k = 1
+        local a = 2
+        for _ = 1, tonumber(ARGV[1]) do
+            local scores, exact, sum, ranked, ranked_exact, names, metadata, replays, submitted = KEYS[k], KEYS[k + 1], KEYS[k + 2], KEYS[k + 3], KEYS[k + 4], KEYS[k + 5], KEYS[k + 6], KEYS[k + 7], KEYS[k + 8]
+            local member, display_name, mode, score, value, meta, submitted_at, order = ARGV[a], ARGV[a + 1], ARGV[a + 2], ARGV[a + 3], ARGV[a + 4], ARGV[a + 5], ARGV[a + 6], ARGV[a + 7]
+            local hidden = ARGV[a + 8] == "1"
+            local periods = tonumber(ARGV[a + 9])
+            k = k + 9
+            a = a + 10
+
+            local changed = insert(scores, exact, sum, mode, score, value, member)
+            redis.call("HSET", names, member, display_name)
+            if submitted_at ~= "" then
+                redis.call("HSET", submitted, member, submitted_at)
+            end
+
+            local period_positions = {}
+            for _ = 1, periods do
+                local period, period_exact = KEYS[k], KEYS[k + 1]
+                local period_position
+                if hidden then
+                    period_position = rank(period, value ~= "" and period_exact or nil, order == "Descending", score, value, member)
+                else
+                    insert(period, period_exact, nil, mode, score, value, member)
+                    redis.call("EXPIREAT", period, ARGV[a])
+                    redis.call("EXPIREAT", period_exact, ARGV[a])
+                    local period_current = redis.call("ZSCORE", period, member)
+                    local period_value = redis.call("HGET", period_exact, member)
+                    period_position = rank(period, value ~= "" and period_exact or nil, order == "Descending", period_current, period_value, member)
+                end
+                table.insert(period_positions, period_position)
+                k = k + 2
+                a = a + 1
+            end
+
+            local detached = false
+            if changed > 0 then
+                if meta ~= "" then
+                    redis.call("HSET", metadata, member, meta)
+                else
+                    redis.call("HDEL", metadata, member)
+                end
+                detached = redis.call("HGET", replays, member)
+                redis.call("HDEL", replays, member)
+            end
+
+            local current = redis.call("ZSCORE", scores, member)
+            local current_value = redis.call("HGET", exact, member)
+            local position = rank(ranked, value ~= "" and ranked_exact or nil, order == "Descending", current, current_value, member)
+
+            table.insert(results, {changed, current, current_value, position, detached, period_positions})
+        end
+        return results
+    "#].concat());
+}
+
+
+/// The properties of a board needed to accept submissions, retrieved once per board.
+#[derive(Clone, Debug)]
+pub(crate) struct SubmissionTarget {
+    /// The name of the board.
+    pub(crate) board: String,
+    /// The sorting order of the board.
+    pub(crate) order: SortingOrder,
+    /// The mode of the board.
+    pub(crate) mode: BoardMode,
+    /// Which numbers the board accepts as scores.
+    pub(crate) kind: ScoreKind,
+    /// The columns making up the scores of the board, if it has more than a single number.
+    pub(crate) schema: Option<ScoreSchema>,
+    /// Whether the board is accepting submissions, as of now.
+    pub(crate) state: BoardState,
+    /// The UNIX timestamp at which the submitted scores were achieved.
+    pub(crate) at: i64,
+    /// Whether the submitted scores were achieved in the past, and are being synchronized only now.
+    pub(crate) backdated: bool,
+    /// The archived season the submitted scores belong to, if they were achieved before it ended.
+    pub(crate) season: Option<String>,
+    /// The key of the sorted set the submitted scores should be written to, unless hidden.
+    pub(crate) scores_key: String,
+    /// The period leaderboards being filled at the time.
+    pub(crate) periods: Vec<PeriodTarget>,
+}
+
+
+/// A period leaderboard the submitted scores of a [`SubmissionTarget`] belong to.
+#[derive(Clone, Debug)]
+pub(crate) struct PeriodTarget {
+    /// The tracked period.
+    pub(crate) period: Period,
+    /// The identifier of the period the scores were achieved in.
+    pub(crate) id: String,
+    /// The key of the sorted set of the scores of the period.
+    pub(crate) scores_key: String,
+    /// The UNIX timestamp at which the period leaderboard should expire.
+    pub(crate) expire_at: i64,
+}
+
+
+/// Retrieve the [`SubmissionTarget`] of `board`, ensuring that it is accepting submissions.
+pub(crate) async fn get_submission_target(rconn: &mut redis::aio::Connection, board: &str) -> Result<SubmissionTarget, outcome::RequestTuple> {
+    get_submission_target_at(rconn, board, chrono::Utc::now().timestamp()).await
+}
+
+
+/// Retrieve the [`SubmissionTarget`] of `board` for scores achieved at the UNIX timestamp `at`, ensuring that it was accepting submissions at the time.
+///
+/// Scores achieved before the end of a season are submitted to its archive, and period leaderboards that already expired are skipped.
+pub(crate) async fn get_submission_target_at(rconn: &mut redis::aio::Connection, board: &str, at: i64) -> Result<SubmissionTarget, outcome::RequestTuple> {
+    let order_key = format!("board:{board}:order");
+    let periods_key = format!("board:{board}:periods");
+    let seasons_key = format!("board:{board}:seasons");
+
+    let now = chrono::Utc::now().timestamp();
+    let backdated = at < now;
+
+    let state = get_board_state(rconn, board).await?;
+    state.ensure_open_at_or_423(at)?;
+
+    log::trace!("Determining sorting order...");
+    let order = rconn.get::<&str, Option<String>>(&order_key).await
+        .map_err(outcome::redis_cmd_failed)?
+        .ok_or_else(|| (StatusCode::NOT_FOUND, outcome::req_error!("No such board")))?;
+    let order = SortingOrder::try_from(order.as_str())
+        .map_err(|_| outcome::redis_unexpected_behaviour())?;
+    log::trace!("Sorting order is: {order:?}");
+
+    let mode = get_board_mode(rconn, board).await?;
+
+    let kind = get_score_kind(rconn, board).await?;
+
+    let schema = get_score_schema(rconn, board).await?;
+
+    log::trace!("Determining tracked periods...");
+    let periods = rconn.smembers::<&str, Vec<String>>(&periods_key).await
+        .map_err(outcome::redis_cmd_failed)?;
+
+    let then = chrono::Utc.timestamp_opt(at, 0).single()
+        .ok_or_else(|| (StatusCode::UNPROCESSABLE_ENTITY, outcome::req_error!("Timestamp is out of range")))?
+        .with_timezone(&*config::PERIODS_TIMEZONE);
+    let periods = periods.iter()
+        .map(|period| {
+            let period = Period::try_from(period.as_str())
+                .map_err(|_| outcome::redis_unexpected_behaviour())?;
+            let id = period.id_at(&then);
+            let scores_key = format!("board:{board}:scores:{id}");
+            let expire_at = period.end_at(&then).timestamp() + *config::PERIODS_RETENTION_SECONDS;
+            Ok(PeriodTarget {period, id, scores_key, expire_at})
+        })
+        .filter(|period: &Result<PeriodTarget, outcome::RequestTuple>| !matches!(period, Ok(PeriodTarget {expire_at, ..}) if *expire_at <= now))
+        .collect::<Result<Vec<PeriodTarget>, outcome::RequestTuple>>()?;
+
+    let season = match backdated {
+        true => {
+            log::trace!("Determining the season the scores belong to...");
+            rconn.zrangebyscore_limit::<&str, String, &str, Vec<String>>(&seasons_key, format!("({at}"), "+inf", 0, 1).await
+                .map_err(outcome::redis_cmd_failed)?
+                .pop()
+        },
+        false => None,
+    };
+    log::trace!("Season is: {season:?}");
+
+    let scores_key = match &season {
+        Some(season) => format!("board:{board}:scores:season:{season}"),
+        None => format!("board:{board}:scores"),
+    };
+
+    Ok(SubmissionTarget {board: board.to_string(), order, mode, kind, schema, state, at, backdated, season, scores_key, periods})
+}
+
+
+/// Convert a submitted [`ScoreValue`] into the score to store on a board with the given `schema`, `kind` and `order`.
+///
+/// Boards without a [`ScoreSchema`] only accept single numbers, and [`ScoreKind::Integer`] boards only accept integers.
+pub(crate) fn score_of_or_422(schema: Option<&ScoreSchema>, kind: ScoreKind, order: SortingOrder, value: &ScoreValue) -> Result<ScoreNumber, outcome::RequestTuple> {
+    log::trace!("Ensuring the score matches the columns and kind of the board...");
+    match (schema, kind, value) {
+        (Some(schema), _, value) => schema.score_of(value, order)
+            .map(ScoreNumber::Float)
+            .map_err(|violation| (StatusCode::UNPROCESSABLE_ENTITY, outcome::req_error!((violation.message())))),
+        (None, _, ScoreValue::Columns(_)) => Err((StatusCode::UNPROCESSABLE_ENTITY, outcome::req_error!("Board does not have multiple score columns"))),
+        (None, ScoreKind::Integer, ScoreValue::Integer(score)) => Ok(ScoreNumber::Integer(*score)),
+        (None, ScoreKind::Integer, ScoreValue::Single(_)) => Err((StatusCode::UNPROCESSABLE_ENTITY, outcome::req_error!("Board only accepts integer scores between -2^63 and 2^63-1"))),
+        (None, ScoreKind::Float, ScoreValue::Integer(score)) => Ok(ScoreNumber::Float(*score as f64)),
+        (None, ScoreKind::Float, ScoreValue::Single(score)) => Ok(ScoreNumber::Float(*score)),
+    }
+}
+
+
+impl SubmissionTarget {
+    /// Decode a stored score into the values of its columns, if the board has a [`ScoreSchema`].
+    pub(crate) fn columns_of(&self, score: ScoreNumber) -> Option<Vec<f64>> {
+        self.schema.as_ref().map(|schema| schema.decode(score.as_f64(), self.order))
+    }
+}
+
+
+/// A score that passed all checks and is ready to be written to its board.
+#[derive(Clone, Debug)]
+pub(crate) struct Submission {
+    /// The normalized name of the player, or the id of the entry on [`BoardMode::Initials`] boards.
+    pub(crate) player: String,
+    /// The name of the player, as it will be displayed.
+    pub(crate) display_name: String,
+    /// The submitted score.
+    pub(crate) score: ScoreNumber,
+    /// The key of the sorted set the score will be written to.
+    pub(crate) scores_key: String,
+    /// The [`ZADD`](https://redis.io/commands/zadd/) mode to use when writing the score.
+    pub(crate) zadd_mode: String,
+    /// Whether the player is shadow-banned, and the score should be hidden.
+    pub(crate) shadow: bool,
+    /// When the entry was submitted, if it is a separate entry of a [`BoardMode::Initials`] board.
+    pub(crate) submitted_at: Option<i64>,
+    /// The metadata of the run, serialized as JSON, to keep for as long as the score counts.
+    pub(crate) metadata: Option<String>,
+    /// Where the submission came from.
+    pub(crate) origin: Origin,
+}
+
+
+/// Check that `player` can submit a score to `target`, returning the [`Submission`] to write.
+///
+/// The submitter is expected to have already been authorized, and the name of the player to follow the name policy.
+pub(crate) async fn prepare_submission(rconn: &mut redis::aio::Connection, target: &SubmissionTarget, player: String, display_name: String, score: &ScoreValue, origin: Origin) -> Result<Submission, outcome::RequestTuple> {
+    let board = &target.board;
+    let names_key = names_key(board, target.season.as_deref());
+
+    let score = score_of_or_422(target.schema.as_ref(), target.kind, target.order, score)?;
+
+    let ban = get_ban_kind(rconn, board, &player).await?;
+    if let Some(BanKind::Ban) = ban {
+        log::trace!("Player is banned, forbidding...");
+        return Err((StatusCode::FORBIDDEN, outcome::req_error!("Player is banned from this board")))
+    }
+    let shadow = ban.is_some();
+
+    let (player, display_name, zadd_mode, submitted_at) = match target.mode {
+        BoardMode::Players => {
+            log::trace!("Ensuring the player name does not collide with another player...");
+            let stored_name = rconn.hget::<&str, &str, Option<String>>(&names_key, &player).await
+                .map_err(outcome::redis_cmd_failed)?;
+            if let Some(stored_name) = stored_name {
+                if !config::PLAYER_NAMES.is_same_name(&stored_name, &display_name) {
+                    log::trace!("{display_name:?} collides with {stored_name:?}, refusing...");
+                    return Err((StatusCode::CONFLICT, outcome::req_error!("Player name collides with the name of another player")))
+                }
+            }
+
+            // Scores of archived seasons are hidden separately, without touching the live ones.
+            if target.season.is_none() {
+                match shadow {
+                    true => hide_score(rconn, board, &player, target.order).await,
+                    false => restore_score(rconn, board, &player, target.order).await,
+                }.map_err(outcome::redis_cmd_failed)?;
+            }
+
+            (player, display_name, target.order.zadd_mode(), None)
+        },
+        BoardMode::Initials => {
+            let initials = display_name.to_kebab_uppercase();
+
+            log::trace!("Ensuring {initials:?} are valid initials...");
+            if initials.chars().count() != 3 || initials.contains('-') {
+                return Err((StatusCode::UNPROCESSABLE_ENTITY, outcome::req_error!("Initials must be exactly three letters or digits")))
+            }
+
+            log::trace!("Generating a new entry id...");
+            let now = chrono::Utc::now();
+            let token = SecureToken::new_or_500()?;
+            let id = format!("{}-{}", now.timestamp_millis(), &token.0[..8]).to_lowercase();
+            log::trace!("Entry id is: {id:?}");
+
+            (id, initials, "NX".to_string(), Some(target.at))
+        },
+    };
+
+    let scores_key = match (shadow, &target.season) {
+        (true, None) => {
+            log::trace!("Player is shadow-banned, hiding the score...");
+            shadow_scores_key(board)
+        },
+        (true, Some(season)) => {
+            log::trace!("Player is shadow-banned, hiding the score from the archived season...");
+            format!("{}:season:{season}", shadow_scores_key(board))
+        },
+        (false, _) => target.scores_key.clone(),
+    };
+
+    Ok(Submission {player, display_name, score, scores_key, zadd_mode, shadow, submitted_at, metadata: None, origin})
+}
+
+
+impl Submission {
+    /// Add the keys and the arguments writing the score to `target` to the given invocation of [`SUBMIT_SCRIPT`].
+    ///
+    /// Hidden scores are ranked as if they were visible, so that shadow-banned players do not notice they are.
+    fn insert_to(&self, invocation: &mut redis::ScriptInvocation, target: &SubmissionTarget) {
+        let board = &target.board;
+        let season = target.season.as_deref();
+
+        log::trace!("Inserting score: {:?}", self.score);
+        invocation
+            .key(&self.scores_key)
+            .key(exact_scores_key(&self.scores_key))
+            .key(sum_scores_key(&self.scores_key))
+            .key(&target.scores_key)
+            .key(exact_scores_key(&target.scores_key))
+            .key(names_key(board, season))
+            .key(metadata_key(board, season))
+            .key(replays_key(board, season))
+            .key(submitted_key(board, season))
+            .arg(&self.player)
+            .arg(&self.display_name)
+            .arg(&self.zadd_mode)
+            .arg(self.score.as_f64())
+            .arg(match self.score {
+                ScoreNumber::Integer(exact) => exact.to_string(),
+                ScoreNumber::Float(_) => String::new(),
+            })
+            .arg(self.metadata.as_deref().unwrap_or(""))
+            .arg(self.submitted_at.map(|submitted_at| submitted_at.to_string()).unwrap_or_default())
+            .arg(Into::<&str>::into(target.order))
+            .arg(if self.shadow { "1" } else { "0" })
+            .arg(target.periods.len());
+
+        for PeriodTarget {scores_key, expire_at, ..} in target.periods.iter() {
+            log::trace!("Inserting score in {scores_key:?}, expiring at {expire_at}...");
+            invocation
+                .key(scores_key)
+                .key(exact_scores_key(scores_key))
+                .arg(expire_at);
+        }
+    }
+
+    /// Queue the commands recording the submission in the audit logs and notifying it to webhooks and live subscribers in the given pipeline.
+    ///
+    /// Only scores improving the live board are notified.
+    pub(crate) fn announce_to(&self, pipe: &mut redis::Pipeline, target: &SubmissionTarget, changed: bool, score: ScoreNumber, rank: usize) {
+        let mut fields = vec![
+            ("player", self.player.clone()),
+            ("score", self.score.to_string()),
+            ("changed", changed.to_string()),
+            ("hidden", self.shadow.to_string()),
+        ];
+        if target.backdated {
+            fields.push(("achieved_at", target.at.to_string()));
+        }
+        if let Some(season) = &target.season {
+            fields.push(("season", season.clone()));
+        }
+        audit_to(pipe, Some(&target.board), &self.origin, "ScoreSubmitted", &fields);
+
+        if changed && !self.shadow && target.season.is_none() {
+            log::trace!("Emitting event for webhooks and live subscribers...");
+            emit_to(pipe, &target.board, BoardEvent::ScoreImproved {
+                name: self.player.clone(),
+                display_name: self.display_name.clone(),
+                score,
+                columns: target.columns_of(score),
+                rank,
+            });
+        }
+    }
+
+    /// Build the response describing the submission to `target`, given what [`write_submissions`] returned for it.
+    pub(crate) fn into_response(self, target: &SubmissionTarget, written: WrittenSubmission) -> RouteScoreResponse {
+        let periods = target.periods.iter()
+            .zip(written.period_ranks)
+            .map(|(PeriodTarget {period, id, ..}, rank)| PeriodRankObject {period: *period, id: id.clone(), rank})
+            .collect::<Vec<PeriodRankObject>>();
+
+        RouteScoreResponse {
+            name: self.player,
+            display_name: self.display_name,
+            score: written.score,
+            columns: target.columns_of(written.score),
+            rank: written.rank,
+            periods: (!periods.is_empty()).then_some(periods),
+            submitted_at: self.submitted_at,
+            season: target.season.clone(),
+            metadata: None,
+            replay: None,
+        }
+    }
+}
+
+
+/// What writing a [`Submission`] with [`write_submissions`] resulted in.
+#[derive(Clone, Debug)]
+pub(crate) struct WrittenSubmission {
+    /// Whether the score changed.
+    pub(crate) changed: bool,
+    /// The current score of the entry.
+    pub(crate) score: ScoreNumber,
+    /// The rank of the entry on the board, or on the archived season it was submitted to.
+    pub(crate) rank: usize,
+    /// The rank of the entry on each of the [`SubmissionTarget::periods`], in the same order.
+    pub(crate) period_ranks: Vec<usize>,
+}
+
+
+/// Write the given [`Submission`]s in as few round trips as possible, returning whether each score changed, along with its current value and ranks.
+///
+/// Changed entries have their metadata replaced, and their replays discarded from `blobs`, atomically with the scores.
+///
+/// Fails only if nothing was written: once the scores are, failures to record and notify them are logged instead, so that the results are never lost.
+pub(crate) async fn write_submissions(rconn: &mut redis::aio::Connection, blobs: &dyn BlobStore, submissions: &[(&SubmissionTarget, &Submission)]) -> Result<Vec<WrittenSubmission>, redis::RedisError> {
+    if submissions.is_empty() {
+        return Ok(vec![])
+    }
+
+    log::trace!("Writing {} submissions...", submissions.len());
+    let mut invocation = SUBMIT_SCRIPT.prepare_invoke();
+    invocation.arg(submissions.len());
+    for (target, submission) in submissions.iter() {
+        submission.insert_to(&mut invocation, target);
+    }
+    let written = invocation
+        .invoke_async::<redis::aio::Connection, Vec<(bool, f64, Option<String>, usize, Option<String>, Vec<usize>)>>(rconn).await?;
+
+    let mut detached = Vec::with_capacity(written.len());
+    let results: Vec<WrittenSubmission> = written.into_iter()
+        .zip(submissions.iter())
+        .map(|((changed, score, exact, rank, replay, period_ranks), (_, submission))| {
+            detached.push(replay);
+            let score = match (submission.score, exact.and_then(|exact| exact.parse::<i64>().ok())) {
+                (ScoreNumber::Integer(_), Some(exact)) => ScoreNumber::Integer(exact),
+                _ => ScoreNumber::Float(score),
+            };
+            WrittenSubmission {changed, score, rank, period_ranks}
+        })
+        .collect();
+
+    delete_replays(blobs, detached).await;
+
+    log::trace!("Announcing submissions...");
+    let mut pipe = redis::pipe();
+    for ((target, submission), written) in submissions.iter().zip(results.iter()) {
+        submission.announce_to(&mut pipe, target, written.changed, written.score, written.rank);
+    }
+    if let Err(err) = pipe.query_async::<redis::aio::Connection, ()>(rconn).await {
+        log::error!("Failed to announce {} written submissions: {err:#?}", submissions.len());
+    }
+
+    Ok(results)
+}
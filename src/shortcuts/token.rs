@@ -1,9 +1,55 @@
+use async_trait::async_trait;
 use axum::http::{HeaderMap, StatusCode};
+use redis::AsyncCommands;
 use regex::Regex;
 use crate::outcome;
+use crate::config;
+use crate::shortcuts::audit::Actor;
+use crate::utils::auth::SubmissionAuth;
 use crate::utils::token::SecureToken;
 
 
+/// Key of the secret token ids are derived with, if the `TOKEN_ID_SECRET` environment variable is not set.
+pub(crate) const TOKEN_ID_SECRET_KEY: &str = "token-id-secret";
+
+
+lazy_static::lazy_static! {
+    /// The secret [token ids](crate::utils::token::token_id) are derived with.
+    ///
+    /// It is the `TOKEN_ID_SECRET` environment variable, if set, or a secret generated once and stored in Redis, so that every server instance shares it across restarts.
+    pub(crate) static ref TOKEN_ID_SECRET: String = match &*config::TOKEN_ID_SECRET {
+        Some(secret) => secret.clone(),
+        None => stored_token_id_secret(),
+    };
+}
+
+
+/// Get the secret token ids are derived with from Redis, generating and storing it if no server instance did yet.
+///
+/// If Redis cannot be reached, a secret is generated for this run only, so that the server can start anyway.
+fn stored_token_id_secret() -> String {
+    let generated = SecureToken::new()
+        .expect("to be able to generate the secret of token ids")
+        .0;
+
+    log::debug!("Retrieving the secret of token ids from Redis...");
+    let stored = redis::Client::open(&**config::REDIS_CONN)
+        .and_then(|rclient| rclient.get_connection())
+        .and_then(|mut rconn| {
+            redis::cmd("SET").arg(TOKEN_ID_SECRET_KEY).arg(&generated).arg("NX").query::<()>(&mut rconn)?;
+            redis::cmd("GET").arg(TOKEN_ID_SECRET_KEY).query::<String>(&mut rconn)
+        });
+
+    match stored {
+        Ok(secret) => secret,
+        Err(err) => {
+            log::warn!("TOKEN_ID_SECRET is not set and the stored one could not be retrieved, token ids will change on restart: {err}");
+            generated
+        },
+    }
+}
+
+
 pub trait Authorize<'h> {
     fn get_authorization_or_401(&'h self, scheme: &str) -> Result<&'h str, outcome::RequestTuple>;
 }
@@ -47,4 +93,102 @@ impl Generate for SecureToken {
         SecureToken::new()
             .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, outcome::req_error!("Could not generate token")))
     }
-}
\ No newline at end of file
+}
+
+
+/// Retrieve the [`SubmissionAuth`] of the given board.
+///
+/// Boards created before player authentication was introduced have none stored, and are treated as [`SubmissionAuth::Board`] boards.
+pub(crate) async fn get_submission_auth(rconn: &mut redis::aio::Connection, board: &str) -> Result<SubmissionAuth, outcome::RequestTuple> {
+    let auth_key = format!("board:{board}:auth");
+
+    log::trace!("Determining board submission authentication...");
+    let auth = rconn.get::<&str, Option<String>>(&auth_key).await
+        .map_err(outcome::redis_cmd_failed)?
+        .map(|auth| SubmissionAuth::try_from(auth.as_str()))
+        .transpose()
+        .map_err(|_| outcome::redis_unexpected_behaviour())?
+        .unwrap_or_default();
+    log::trace!("Board submission authentication is: {auth:?}");
+
+    Ok(auth)
+}
+
+
+#[async_trait]
+pub(crate) trait CheckBoardToken {
+    async fn check_board_token_or_403(&mut self, board: &str, token: &str) -> Result<(), outcome::RequestTuple>;
+    async fn check_board_or_player_token_or_403(&mut self, board: &str, player: &str, token: &str) -> Result<Actor, outcome::RequestTuple>;
+    async fn check_board_or_admin_token_or_403(&mut self, board: &str, token: &str) -> Result<Actor, outcome::RequestTuple>;
+    async fn check_manager_token_or_403(&mut self, board: Option<&str>, token: &str) -> Result<Actor, outcome::RequestTuple>;
+}
+
+#[async_trait]
+impl CheckBoardToken for redis::aio::Connection {
+    async fn check_board_token_or_403(&mut self, board: &str, token: &str) -> Result<(), outcome::RequestTuple> {
+        let token_key = format!("board:{board}:token");
+
+        log::trace!("Checking if the token exists and matches...");
+        let btoken = self.get::<&str, Option<String>>(&token_key).await
+            .map_err(outcome::redis_cmd_failed)?;
+
+        let btoken = btoken
+            .ok_or_else(|| {
+                log::trace!("Token is not set, board does not exist...");
+                (StatusCode::NOT_FOUND, outcome::req_error!("No such board"))
+            })?;
+
+        if btoken != token {
+            log::trace!("Token does not match, forbidding...");
+            return Err((StatusCode::FORBIDDEN, outcome::req_error!("Invalid board token")))
+        }
+
+        Ok(())
+    }
+    async fn check_board_or_player_token_or_403(&mut self, board: &str, player: &str, token: &str) -> Result<Actor, outcome::RequestTuple> {
+        match self.check_board_token_or_403(board, token).await {
+            Err((StatusCode::FORBIDDEN, _)) => {},
+            result => return result.map(|_| Actor::Board),
+        };
+
+        let players_key = format!("board:{board}:players");
+
+        if let SubmissionAuth::Board = get_submission_auth(self, board).await? {
+            log::trace!("Board does not authenticate players, forbidding...");
+            return Err((StatusCode::FORBIDDEN, outcome::req_error!("Invalid board token")))
+        }
+
+        log::trace!("Checking if the player token exists and matches...");
+        let ptoken = self.hget::<&str, &str, Option<String>>(&players_key, player).await
+            .map_err(outcome::redis_cmd_failed)?;
+
+        if ptoken.as_deref() != Some(token) {
+            log::trace!("Player token does not match, forbidding...");
+            return Err((StatusCode::FORBIDDEN, outcome::req_error!("Invalid board or player token")))
+        }
+
+        Ok(Actor::Player)
+    }
+    async fn check_board_or_admin_token_or_403(&mut self, board: &str, token: &str) -> Result<Actor, outcome::RequestTuple> {
+        if config::ADMIN_TOKEN.as_deref() == Some(token) {
+            log::trace!("Token is the admin token, allowing...");
+            return Ok(Actor::Admin)
+        }
+
+        self.check_board_token_or_403(board, token).await?;
+        Ok(Actor::Board)
+    }
+
+    async fn check_manager_token_or_403(&mut self, board: Option<&str>, token: &str) -> Result<Actor, outcome::RequestTuple> {
+        match board {
+            Some(board) => self.check_board_or_admin_token_or_403(board, token).await,
+            None => match config::ADMIN_TOKEN.as_deref() == Some(token) {
+                true => Ok(Actor::Admin),
+                false => {
+                    log::trace!("Token is not the admin token, forbidding...");
+                    Err((StatusCode::FORBIDDEN, outcome::req_error!("Invalid admin token")))
+                },
+            },
+        }
+    }
+}
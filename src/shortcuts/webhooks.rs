@@ -0,0 +1,269 @@
+//! Module defining the events happening on boards, and how they are delivered to webhooks and live subscribers.
+
+use std::net::SocketAddr;
+use hmac::{Hmac, Mac};
+use serde::Serialize;
+use serde::Deserialize;
+use sha2::Sha256;
+use crate::utils::address::is_public;
+use crate::utils::kind::ScoreNumber;
+
+
+/// Key of the list containing the events waiting to be matched against the webhooks of their board.
+pub(crate) const WEBHOOK_EVENTS_KEY: &str = "webhooks:events";
+
+/// Key of the list containing the event being matched against the webhooks of its board, until its deliveries are scheduled.
+pub(crate) const WEBHOOK_EVENTS_PROCESSING_KEY: &str = "webhooks:events:processing";
+
+/// Key of the lock ensuring that a single server instance matches the queued events at a time.
+pub(crate) const WEBHOOK_EVENTS_LOCK_KEY: &str = "webhooks:events:lock";
+
+/// Key of the sorted set containing the pending [`Delivery`]s, by the UNIX timestamp at which they should be attempted.
+pub(crate) const WEBHOOK_DELIVERIES_KEY: &str = "webhooks:deliveries";
+
+/// How many entries are kept in the delivery log of each webhook.
+pub(crate) const DELIVERY_LOG_LENGTH: isize = 100;
+
+
+/// Something that happened on a board, which webhooks may be notified about.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub(crate) enum BoardEvent {
+    /// A player improved their score, or, on [`Initials`](crate::utils::mode::BoardMode::Initials) boards, a new entry was submitted.
+    ScoreImproved {
+        /// The normalized name of the player.
+        name: String,
+        /// The name of the player, as it was submitted.
+        display_name: String,
+        /// The new score of the player.
+        score: ScoreNumber,
+        /// The values of the columns of the new score, if the board has multiple.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        columns: Option<Vec<f64>>,
+        /// The new position of the player, zero-based.
+        rank: usize,
+    },
+    /// A moderator corrected the score of a player, or the hidden score of a player was restored after their shadow-ban was lifted.
+    ///
+    /// Only sent to live subscribers.
+    ScoreChanged {
+        /// The normalized name of the player.
+        name: String,
+        /// The name of the player, as it was submitted.
+        display_name: String,
+        /// The new score of the player.
+        score: ScoreNumber,
+        /// The values of the columns of the new score, if the board has multiple.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        columns: Option<Vec<f64>>,
+        /// The new position of the player, zero-based.
+        rank: usize,
+        /// The previous position of the player, zero-based, if their score was visible.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        previous_rank: Option<usize>,
+    },
+    /// The score of a player was removed from the visible ones, either by a moderator or because the player was shadow-banned.
+    ///
+    /// Only sent to live subscribers.
+    ScoreRemoved {
+        /// The normalized name of the player.
+        name: String,
+        /// The position the removed score had, zero-based.
+        rank: usize,
+    },
+    /// The live scores of the board were archived as a season, leaving the board empty.
+    BoardReset {
+        /// The name of the archived season.
+        season: String,
+    },
+}
+
+impl BoardEvent {
+    /// Check whether webhooks may be notified about the event, as only some kinds of events are delivered to them.
+    pub(crate) fn notifies_webhooks(&self) -> bool {
+        matches!(self, Self::ScoreImproved {..} | Self::BoardReset {..})
+    }
+}
+
+
+/// A [`BoardEvent`], along with where and when it happened.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(crate) struct QueuedEvent {
+    /// The board the event happened on.
+    pub(crate) board: String,
+    /// The UNIX timestamp at which the event happened.
+    pub(crate) at: i64,
+    /// What happened.
+    #[serde(flatten)]
+    pub(crate) event: BoardEvent,
+}
+
+
+/// Script queueing an event for the webhooks of its board, only if the board has any.
+///
+/// - `KEYS[1]`: the hash of the webhooks of the board
+/// - `KEYS[2]`: the [list of the queued events](WEBHOOK_EVENTS_KEY)
+/// - `ARGV[1]`: the [`QueuedEvent`] to queue, serialized as JSON
+///
+/// Run through `EVAL`, as pipelines cannot invoke [`redis::Script`]s.
+const QUEUE_EVENT_LUA: &str = r#"
+    if redis.call("EXISTS", KEYS[1]) == 1 then
+        redis.call("LPUSH", KEYS[2], ARGV[1])
+    end
+"#;
+
+
+/// Get the Pub/Sub channel the events of `board` are published on, for the [live subscribers](crate::routes::live).
+pub(crate) fn events_channel(board: &str) -> String {
+    format!("board:{board}:events")
+}
+
+
+/// Queue the commands emitting `event` for the webhooks and the live subscribers of `board` in the given pipeline.
+///
+/// Events are only matched against the webhooks later by the [webhooks task](crate::tasks::webhooks), so that emitting them is as fast as possible, and are not queued at all if the board has no webhooks, or if [no webhook may be notified about them](BoardEvent::notifies_webhooks).
+///
+/// Live subscribers receive the event through Redis Pub/Sub, so that it reaches them whichever server instance they are connected to.
+pub(crate) fn emit_to(pipe: &mut redis::Pipeline, board: &str, event: BoardEvent) {
+    let notifies_webhooks = event.notifies_webhooks();
+    let queued = QueuedEvent {
+        board: board.to_string(),
+        at: chrono::Utc::now().timestamp(),
+        event,
+    };
+    let queued = serde_json::to_string(&queued).expect("event to be serializable");
+
+    if notifies_webhooks {
+        pipe.cmd("EVAL").arg(QUEUE_EVENT_LUA).arg(2).arg(webhooks_key(board)).arg(WEBHOOK_EVENTS_KEY).arg(&queued).ignore();
+    }
+    pipe.publish(events_channel(board), &queued).ignore();
+}
+
+
+/// A kind of [`BoardEvent`] a webhook can subscribe to.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) enum WebhookFilter {
+    /// A score entering the top positions of the board.
+    TopEntry,
+    /// A player improving their own score.
+    PersonalBest,
+    /// The board being reset at the end of a season.
+    BoardReset,
+}
+
+
+/// A webhook subscribed to the events of a board, as stored in [Redis].
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(crate) struct WebhookObject {
+    /// The URL the events are `POST`ed to.
+    pub(crate) url: String,
+    /// The secret used to sign the payloads.
+    pub(crate) secret: String,
+    /// The kinds of events the webhook is subscribed to.
+    pub(crate) filters: Vec<WebhookFilter>,
+    /// How many positions count as the top of the board for [`WebhookFilter::TopEntry`].
+    pub(crate) top: usize,
+}
+
+impl WebhookObject {
+    /// Check whether the webhook should be notified about `event`.
+    pub(crate) fn matches(&self, event: &BoardEvent) -> bool {
+        self.filters.iter().any(|filter| match (filter, event) {
+            (WebhookFilter::TopEntry, BoardEvent::ScoreImproved {rank, ..}) => *rank < self.top,
+            (WebhookFilter::PersonalBest, BoardEvent::ScoreImproved {..}) => true,
+            (WebhookFilter::BoardReset, BoardEvent::BoardReset {..}) => true,
+            _ => false,
+        })
+    }
+}
+
+
+/// An attempt to deliver an event to a webhook, as stored in [Redis].
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(crate) struct Delivery {
+    /// A unique identifier of the delivery, which stays the same across attempts.
+    pub(crate) id: String,
+    /// The board the webhook is subscribed to.
+    pub(crate) board: String,
+    /// The id of the webhook to deliver the event to.
+    pub(crate) webhook: String,
+    /// The JSON payload to deliver.
+    pub(crate) payload: String,
+    /// How many attempts have already failed.
+    pub(crate) attempt: u32,
+}
+
+
+/// The outcome of an attempted [`Delivery`], as a serializable struct.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(crate) struct DeliveryLogEntry {
+    /// The id of the delivery.
+    pub(crate) id: String,
+    /// Which attempt this was, starting from `1`.
+    pub(crate) attempt: u32,
+    /// The UNIX timestamp at which the attempt was made.
+    pub(crate) at: i64,
+    /// The status code the webhook responded with, if it responded at all.
+    pub(crate) status: Option<u16>,
+    /// Why the attempt failed, if it did.
+    pub(crate) error: Option<String>,
+    /// The UNIX timestamp of the next attempt, if the delivery will be retried.
+    pub(crate) retry_at: Option<i64>,
+}
+
+
+/// Get the key of the hash containing the webhooks of `board`, by id.
+pub(crate) fn webhooks_key(board: &str) -> String {
+    format!("board:{board}:webhooks")
+}
+
+
+/// Get the key of the list containing the latest [`DeliveryLogEntry`]s of a webhook of `board`.
+pub(crate) fn delivery_log_key(board: &str, webhook: &str) -> String {
+    format!("board:{board}:webhooks:{webhook}:deliveries")
+}
+
+
+/// Resolve the host of the webhook `url`, ensuring that it only points to [public addresses](is_public).
+///
+/// Returns the parsed URL and the addresses it resolved to, which requests should be pinned to, so that the host cannot resolve to a different address when the request is made.
+pub(crate) async fn resolve_public_url(url: &str) -> Result<(reqwest::Url, Vec<SocketAddr>), String> {
+    let url = reqwest::Url::parse(url)
+        .map_err(|err| format!("Webhook URL is invalid: {err}"))?;
+    let host = url.host_str()
+        .ok_or_else(|| "Webhook URL has no host".to_string())?
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .to_string();
+    let port = url.port_or_known_default()
+        .ok_or_else(|| "Webhook URL has no port".to_string())?;
+
+    log::trace!("Resolving {host:?}...");
+    let addrs = tokio::net::lookup_host((host.as_str(), port)).await
+        .map_err(|err| format!("Webhook host could not be resolved: {err}"))?
+        .collect::<Vec<SocketAddr>>();
+    log::trace!("Host resolved to: {addrs:?}");
+
+    if addrs.is_empty() {
+        return Err("Webhook host could not be resolved".to_string())
+    }
+    if addrs.iter().any(|addr| !is_public(addr.ip())) {
+        return Err("Webhook URL must not point to a private address".to_string())
+    }
+
+    Ok((url, addrs))
+}
+
+
+/// Sign `payload` with `secret`, returning the value of the `X-Signature-256` header.
+pub(crate) fn sign(secret: &str, payload: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+        .expect("HMAC to accept keys of any length");
+    mac.update(payload.as_bytes());
+
+    let signature: String = mac.finalize().into_bytes().iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect();
+
+    format!("sha256={signature}")
+}
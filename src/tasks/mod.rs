@@ -0,0 +1,4 @@
+//! Module containing background tasks running alongside the web server.
+
+pub(crate) mod seasons;
+pub(crate) mod webhooks;
\ No newline at end of file
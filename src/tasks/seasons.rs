@@ -0,0 +1,53 @@
+//! Module defining the task archiving scheduled seasons.
+
+use std::time::Duration;
+use redis::AsyncCommands;
+use crate::shortcuts::season::{archive_scheduled_season, SCHEDULED_SEASONS_KEY};
+
+
+/// How often the scheduled seasons are checked.
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+
+/// Archive every season whose scheduled time has passed.
+///
+/// Each scheduled season is claimed in the same atomic step it is archived in, so that only one server instance archives it, and none loses it.
+async fn archive_due_seasons(rclient: &redis::Client) -> Result<(), redis::RedisError> {
+    let mut rconn = rclient.get_async_connection().await?;
+
+    let now = chrono::Utc::now().timestamp();
+    let due = rconn.zrangebyscore::<&str, &str, i64, Vec<String>>(SCHEDULED_SEASONS_KEY, "-inf", now).await?;
+
+    for scheduled in due {
+        let (board, season) = match serde_json::from_str::<(String, String)>(&scheduled) {
+            Ok(t) => t,
+            Err(err) => {
+                log::warn!("Discarding malformed scheduled season {scheduled:?}: {err}");
+                rconn.zrem::<&str, &str, ()>(SCHEDULED_SEASONS_KEY, &scheduled).await?;
+                continue
+            }
+        };
+
+        match archive_scheduled_season(&mut rconn, &scheduled, &board, &season).await? {
+            None => log::trace!("Scheduled season was claimed by another instance: {scheduled:?}"),
+            Some(false) => log::warn!("Could not archive season {season:?} of {board:?}, as it already exists"),
+            Some(true) => {},
+        }
+    }
+
+    Ok(())
+}
+
+
+/// Periodically [archive the seasons that are due](archive_due_seasons), forever.
+pub(crate) async fn run(rclient: redis::Client) {
+    log::debug!("Starting scheduled seasons task...");
+    let mut interval = tokio::time::interval(POLL_INTERVAL);
+
+    loop {
+        interval.tick().await;
+        if let Err(err) = archive_due_seasons(&rclient).await {
+            log::error!("{err:#?}");
+        }
+    }
+}
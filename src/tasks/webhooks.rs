@@ -0,0 +1,307 @@
+//! Module defining the task delivering events to webhooks.
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use redis::{AsyncCommands, Direction};
+use crate::config;
+use crate::shortcuts::webhooks::{delivery_log_key, resolve_public_url, sign, webhooks_key, Delivery, DeliveryLogEntry, QueuedEvent, WebhookObject, DELIVERY_LOG_LENGTH, WEBHOOK_DELIVERIES_KEY, WEBHOOK_EVENTS_KEY, WEBHOOK_EVENTS_LOCK_KEY, WEBHOOK_EVENTS_PROCESSING_KEY};
+use crate::utils::token::SecureToken;
+
+
+/// How often the queues are checked.
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How many deliveries are attempted at most on every check.
+const BATCH_SIZE: usize = 100;
+
+/// How long the lock on the queued events is held at most, after which another server instance may take over.
+const FAN_OUT_LOCK_SECONDS: usize = 60;
+
+/// How much longer than the timeout of a delivery it stays claimed by the server instance attempting it, before it is attempted again by another one.
+const DELIVERY_LEASE_MARGIN_SECONDS: i64 = 60;
+
+/// The delay before the first retry of a failed delivery, which doubles on every following attempt.
+const RETRY_BASE_SECONDS: i64 = 10;
+
+/// The maximum delay between two attempts of the same delivery.
+const RETRY_MAX_SECONDS: i64 = 3600;
+
+
+lazy_static::lazy_static! {
+    /// Script acquiring the lock on the queued events, requeueing the event left in processing by a server instance which stopped while holding it.
+    ///
+    /// - `KEYS[1]`: the [lock](WEBHOOK_EVENTS_LOCK_KEY)
+    /// - `KEYS[2]`: the [list of the queued events](WEBHOOK_EVENTS_KEY)
+    /// - `KEYS[3]`: the [list of the events in processing](WEBHOOK_EVENTS_PROCESSING_KEY)
+    /// - `ARGV[1]`: the id of the server instance acquiring the lock
+    /// - `ARGV[2]`: for how many seconds the lock should be held at most
+    ///
+    /// Returns whether the lock was acquired.
+    static ref ACQUIRE_EVENTS_SCRIPT: redis::Script = redis::Script::new(r#"
+        if not redis.call("SET", KEYS[1], ARGV[1], "NX", "EX", ARGV[2]) then
+            return 0
+        end
+        while redis.call("LMOVE", KEYS[3], KEYS[2], "LEFT", "RIGHT") do end
+        return 1
+    "#);
+
+    /// Script releasing the lock on the queued events, only if it is still held by the given server instance.
+    ///
+    /// - `KEYS[1]`: the [lock](WEBHOOK_EVENTS_LOCK_KEY)
+    /// - `ARGV[1]`: the id of the server instance releasing the lock
+    static ref RELEASE_EVENTS_SCRIPT: redis::Script = redis::Script::new(r#"
+        if redis.call("GET", KEYS[1]) == ARGV[1] then
+            redis.call("DEL", KEYS[1])
+        end
+    "#);
+
+    /// Script claiming the deliveries that are due, postponing them until the end of the lease, so that other server instances do not attempt them too.
+    ///
+    /// - `KEYS[1]`: the [sorted set of the pending deliveries](WEBHOOK_DELIVERIES_KEY)
+    /// - `ARGV[1]`: the current UNIX timestamp
+    /// - `ARGV[2]`: the UNIX timestamp at which the lease ends
+    /// - `ARGV[3]`: how many deliveries to claim at most
+    ///
+    /// Returns the claimed deliveries.
+    static ref CLAIM_DELIVERIES_SCRIPT: redis::Script = redis::Script::new(r#"
+        local due = redis.call("ZRANGEBYSCORE", KEYS[1], "-inf", ARGV[1], "LIMIT", 0, ARGV[3])
+        for _, delivery in ipairs(due) do
+            redis.call("ZADD", KEYS[1], "XX", ARGV[2], delivery)
+        end
+        return due
+    "#);
+}
+
+
+/// Match the queued events against the webhooks of their board, scheduling a [`Delivery`] for every matching webhook, until none are left.
+///
+/// Only the server instance holding the [lock](WEBHOOK_EVENTS_LOCK_KEY) does so, and each event is kept in [processing](WEBHOOK_EVENTS_PROCESSING_KEY) until its deliveries are scheduled, so that it is not lost if the instance stops.
+async fn fan_out_events(rconn: &mut redis::aio::Connection, instance: &str) -> Result<(), redis::RedisError> {
+    let acquired = ACQUIRE_EVENTS_SCRIPT
+        .key(WEBHOOK_EVENTS_LOCK_KEY)
+        .key(WEBHOOK_EVENTS_KEY)
+        .key(WEBHOOK_EVENTS_PROCESSING_KEY)
+        .arg(instance)
+        .arg(FAN_OUT_LOCK_SECONDS)
+        .invoke_async::<redis::aio::Connection, bool>(rconn).await?;
+    if !acquired {
+        log::trace!("Events are being matched by another instance");
+        return Ok(())
+    }
+
+    let result = fan_out_queued_events(rconn).await;
+
+    RELEASE_EVENTS_SCRIPT
+        .key(WEBHOOK_EVENTS_LOCK_KEY)
+        .arg(instance)
+        .invoke_async::<redis::aio::Connection, ()>(rconn).await?;
+
+    result
+}
+
+
+/// Match the queued events one at a time, while the lock on them is held.
+async fn fan_out_queued_events(rconn: &mut redis::aio::Connection) -> Result<(), redis::RedisError> {
+    let started = Instant::now();
+
+    // Stop well before the lock expires, leaving the rest of the events to the next check.
+    while started.elapsed() < Duration::from_secs(FAN_OUT_LOCK_SECONDS as u64 / 2) {
+        let queued = rconn.lmove::<&str, Option<String>>(WEBHOOK_EVENTS_KEY, WEBHOOK_EVENTS_PROCESSING_KEY, Direction::Right, Direction::Left).await?;
+        let queued = match queued {
+            Some(queued) => queued,
+            None => break,
+        };
+
+        let mut pipe = redis::pipe();
+        pipe.atomic();
+
+        match serde_json::from_str::<QueuedEvent>(&queued) {
+            Ok(event) => schedule_deliveries_to(&mut pipe, rconn, &event, &queued).await?,
+            Err(err) => log::warn!("Discarding malformed event {queued:?}: {err}"),
+        }
+
+        pipe.lrem(WEBHOOK_EVENTS_PROCESSING_KEY, 1, &queued).ignore();
+        pipe.query_async::<redis::aio::Connection, ()>(rconn).await?;
+    }
+
+    Ok(())
+}
+
+
+/// Queue the commands scheduling a [`Delivery`] of `event` to every matching webhook of its board in the given pipeline.
+async fn schedule_deliveries_to(pipe: &mut redis::Pipeline, rconn: &mut redis::aio::Connection, event: &QueuedEvent, queued: &str) -> Result<(), redis::RedisError> {
+    let webhooks = rconn.hgetall::<String, HashMap<String, String>>(webhooks_key(&event.board)).await?;
+
+    let now = chrono::Utc::now().timestamp();
+    for (webhook, object) in webhooks {
+        let object = match serde_json::from_str::<WebhookObject>(&object) {
+            Ok(object) => object,
+            Err(err) => {
+                log::warn!("Skipping malformed webhook {webhook:?} of {:?}: {err}", event.board);
+                continue
+            }
+        };
+
+        if !object.matches(&event.event) {
+            continue
+        }
+
+        let id = match SecureToken::new() {
+            Ok(token) => token.0,
+            Err(err) => {
+                log::error!("Could not generate delivery id: {err}");
+                continue
+            }
+        };
+
+        let delivery = Delivery {
+            id,
+            board: event.board.clone(),
+            webhook,
+            payload: queued.to_string(),
+            attempt: 0,
+        };
+        log::trace!("Scheduling delivery: {delivery:?}");
+        pipe.zadd(WEBHOOK_DELIVERIES_KEY, serde_json::to_string(&delivery).expect("delivery to be serializable"), now).ignore();
+    }
+
+    Ok(())
+}
+
+
+/// Attempt to deliver the payload of `delivery` to `webhook`, returning the status code of the response.
+///
+/// The host of the webhook is resolved again before every attempt, and the request is pinned to the resolved addresses, so that it cannot be pointed to a private address after the webhook was created.
+async fn attempt_delivery(delivery: &Delivery, webhook: &WebhookObject) -> Result<u16, String> {
+    let (url, addrs) = resolve_public_url(&webhook.url).await?;
+
+    let mut http = reqwest::Client::builder()
+        .timeout(Duration::from_secs(*config::WEBHOOKS_TIMEOUT_SECONDS))
+        .redirect(reqwest::redirect::Policy::none());
+    if let Some(host) = url.domain() {
+        http = http.resolve_to_addrs(host, &addrs);
+    }
+    let http = http.build()
+        .map_err(|err| err.to_string())?;
+
+    let response = http.post(url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .header("X-Delivery-Id", &delivery.id)
+        .header("X-Signature-256", sign(&webhook.secret, &delivery.payload))
+        .body(delivery.payload.clone())
+        .send().await
+        .map_err(|err| err.to_string())?;
+
+    let status = response.status();
+    match status.is_success() {
+        true => Ok(status.as_u16()),
+        false => Err(format!("Webhook responded with {status}")),
+    }
+}
+
+
+/// Attempt the deliveries that are due, logging their outcome and rescheduling the failed ones with an exponential backoff.
+///
+/// Each delivery is claimed for the duration of a lease before being attempted, so that only one server instance attempts it, and is only removed from the queue once its outcome is logged, so that it is attempted again if the instance stops.
+async fn attempt_due_deliveries(rconn: &mut redis::aio::Connection) -> Result<(), redis::RedisError> {
+    let now = chrono::Utc::now().timestamp();
+    let lease = (*config::WEBHOOKS_TIMEOUT_SECONDS as i64).saturating_add(DELIVERY_LEASE_MARGIN_SECONDS);
+    let due = CLAIM_DELIVERIES_SCRIPT
+        .key(WEBHOOK_DELIVERIES_KEY)
+        .arg(now)
+        .arg(now.saturating_add(lease))
+        .arg(BATCH_SIZE)
+        .invoke_async::<redis::aio::Connection, Vec<String>>(rconn).await?;
+
+    let mut claimed = vec![];
+    for scheduled in due {
+        let delivery = match serde_json::from_str::<Delivery>(&scheduled) {
+            Ok(delivery) => delivery,
+            Err(err) => {
+                log::warn!("Discarding malformed delivery {scheduled:?}: {err}");
+                rconn.zrem::<&str, &str, ()>(WEBHOOK_DELIVERIES_KEY, &scheduled).await?;
+                continue
+            }
+        };
+
+        let webhook = rconn.hget::<String, &str, Option<String>>(webhooks_key(&delivery.board), &delivery.webhook).await?
+            .and_then(|webhook| serde_json::from_str::<WebhookObject>(&webhook).ok());
+        match webhook {
+            Some(webhook) => claimed.push((scheduled, delivery, webhook)),
+            None => {
+                log::debug!("Discarding delivery to deleted webhook: {delivery:?}");
+                rconn.zrem::<&str, &str, ()>(WEBHOOK_DELIVERIES_KEY, &scheduled).await?;
+            },
+        }
+    }
+
+    let outcomes = futures::future::join_all(
+        claimed.iter().map(|(_, delivery, webhook)| attempt_delivery(delivery, webhook))
+    ).await;
+
+    let mut pipe = redis::pipe();
+    pipe.atomic();
+    for ((scheduled, mut delivery, _), outcome) in claimed.into_iter().zip(outcomes) {
+        delivery.attempt += 1;
+
+        let retry_at = match (&outcome, delivery.attempt < *config::WEBHOOKS_MAX_ATTEMPTS) {
+            (Err(_), true) => {
+                let backoff = RETRY_BASE_SECONDS.saturating_mul(1 << (delivery.attempt - 1).min(16)).min(RETRY_MAX_SECONDS);
+                Some(now + backoff)
+            },
+            _ => None,
+        };
+
+        let entry = DeliveryLogEntry {
+            id: delivery.id.clone(),
+            attempt: delivery.attempt,
+            at: now,
+            status: outcome.as_ref().ok().copied(),
+            error: outcome.err(),
+            retry_at,
+        };
+        log::debug!("Attempted delivery: {entry:?}");
+
+        let log_key = delivery_log_key(&delivery.board, &delivery.webhook);
+        pipe.lpush(&log_key, serde_json::to_string(&entry).expect("delivery log entry to be serializable")).ignore();
+        pipe.ltrim(&log_key, 0, DELIVERY_LOG_LENGTH - 1).ignore();
+        pipe.zrem(WEBHOOK_DELIVERIES_KEY, &scheduled).ignore();
+
+        if let Some(retry_at) = retry_at {
+            pipe.zadd(WEBHOOK_DELIVERIES_KEY, serde_json::to_string(&delivery).expect("delivery to be serializable"), retry_at).ignore();
+        }
+    }
+    pipe.query_async::<redis::aio::Connection, ()>(rconn).await?;
+
+    Ok(())
+}
+
+
+/// Periodically [fan out the queued events](fan_out_events) and [attempt the due deliveries](attempt_due_deliveries), forever.
+pub(crate) async fn run(rclient: redis::Client) {
+    log::debug!("Starting webhooks task...");
+    let mut interval = tokio::time::interval(POLL_INTERVAL);
+
+    let instance = SecureToken::new()
+        .expect("to be able to generate an id for this server instance")
+        .0;
+
+    loop {
+        interval.tick().await;
+
+        let mut rconn = match rclient.get_async_connection().await {
+            Ok(rconn) => rconn,
+            Err(err) => {
+                log::error!("{err:#?}");
+                continue
+            }
+        };
+
+        if let Err(err) = fan_out_events(&mut rconn, &instance).await {
+            log::error!("{err:#?}");
+        }
+        if let Err(err) = attempt_due_deliveries(&mut rconn).await {
+            log::error!("{err:#?}");
+        }
+    }
+}
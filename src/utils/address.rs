@@ -0,0 +1,143 @@
+//! Module defining which network addresses are reachable from the public internet.
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+
+/// Check whether `ip` is a public address, excluding private, loopback, link-local and every other special-purpose range.
+///
+/// Used to prevent requests made on behalf of users, such as webhook deliveries, from reaching the internal network of the server.
+pub fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_v4(ip),
+        IpAddr::V6(ip) => is_public_v6(ip),
+    }
+}
+
+
+/// [`is_public`], for IPv4 addresses.
+fn is_public_v4(ip: Ipv4Addr) -> bool {
+    let [a, b, c, _] = ip.octets();
+
+    !(
+        ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_broadcast()
+        || ip.is_multicast()
+        || ip.is_documentation()
+        // "This network"
+        || a == 0
+        // Shared address space
+        || (a == 100 && (64..128).contains(&b))
+        // IETF protocol assignments
+        || (a == 192 && b == 0 && c == 0)
+        // Benchmarking
+        || (a == 198 && (18..20).contains(&b))
+        // Reserved
+        || a >= 240
+    )
+}
+
+
+/// [`is_public`], for IPv6 addresses.
+///
+/// Addresses embedding an IPv4 address are checked as the IPv4 address they embed, if it can be determined, or refused altogether otherwise.
+fn is_public_v6(ip: Ipv6Addr) -> bool {
+    if let Some(ip) = ip.to_ipv4_mapped() {
+        return is_public_v4(ip)
+    }
+
+    let segments = ip.segments();
+    let embedded = |high: u16, low: u16| Ipv4Addr::from(((high as u32) << 16) | low as u32);
+
+    // IPv4-compatible, deprecated
+    if segments[..6] == [0; 6] && !ip.is_unspecified() && !ip.is_loopback() {
+        return is_public_v4(embedded(segments[6], segments[7]))
+    }
+    // 6to4
+    if segments[0] == 0x2002 {
+        return is_public_v4(embedded(segments[1], segments[2]))
+    }
+
+    !(
+        ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_multicast()
+        // Unique local
+        || (segments[0] & 0xfe00) == 0xfc00
+        // Link-local
+        || (segments[0] & 0xffc0) == 0xfe80
+        // Documentation
+        || (segments[0] == 0x2001 && segments[1] == 0x0db8)
+        // Teredo, which may reach any IPv4 address
+        || (segments[0] == 0x2001 && segments[1] == 0x0000)
+        // IPv4/IPv6 translation, both well-known and local-use, which may reach any IPv4 address
+        || (segments[0] == 0x0064 && segments[1] == 0xff9b)
+        // Discard-only
+        || segments[..4] == [0x0100, 0, 0, 0]
+    )
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn public(ip: &str) -> bool {
+        is_public(ip.parse().unwrap())
+    }
+
+    #[test]
+    fn public_v4() {
+        assert!(public("1.1.1.1"));
+        assert!(public("93.184.216.34"));
+    }
+
+    #[test]
+    fn internal_v4() {
+        assert!(!public("127.0.0.1"));
+        assert!(!public("10.1.2.3"));
+        assert!(!public("172.16.0.1"));
+        assert!(!public("192.168.1.1"));
+        assert!(!public("169.254.169.254"));
+        assert!(!public("0.0.0.0"));
+        assert!(!public("100.64.0.1"));
+        assert!(!public("255.255.255.255"));
+    }
+
+    #[test]
+    fn public_v6() {
+        assert!(public("2606:4700:4700::1111"));
+    }
+
+    #[test]
+    fn internal_v6() {
+        assert!(!public("::1"));
+        assert!(!public("::"));
+        assert!(!public("fd00::1"));
+        assert!(!public("fe80::1"));
+        assert!(!public("::ffff:127.0.0.1"));
+        assert!(!public("::ffff:169.254.169.254"));
+        assert!(!public("64:ff9b::a9fe:a9fe"));
+    }
+
+    #[test]
+    fn embedded_v4() {
+        assert!(!public("::7f00:1"));
+        assert!(!public("::a00:1"));
+        assert!(public("::101:101"));
+        assert!(!public("2002:7f00:1::"));
+        assert!(!public("2002:a00:1::1"));
+        assert!(public("2002:101:101::1"));
+    }
+
+    #[test]
+    fn special_v6() {
+        assert!(!public("2001::1"));
+        assert!(!public("2001:0:4136:e378:8000:63bf:3fff:fdd2"));
+        assert!(!public("64:ff9b:1::a9fe:a9fe"));
+        assert!(!public("100::1"));
+        assert!(!public("2001:db8::1"));
+    }
+}
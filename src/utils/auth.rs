@@ -0,0 +1,39 @@
+//! Module defining and implementing [`SubmissionAuth`].
+
+use serde::Serialize;
+use serde::Deserialize;
+
+
+/// Who is allowed to submit scores to a board.
+#[derive(Copy, Clone, Debug, Default, Serialize, Deserialize)]
+pub enum SubmissionAuth {
+    /// Only the holders of the board token, which may submit scores on behalf of any player.
+    #[default]
+    Board,
+
+    /// The holders of the board token, and registered players, which may only submit their own scores using their player token.
+    Players,
+}
+
+/// How the [`SubmissionAuth`] is stored in [Redis].
+impl From<SubmissionAuth> for &str {
+    fn from(auth: SubmissionAuth) -> Self {
+        match auth {
+            SubmissionAuth::Board   => "Board",
+            SubmissionAuth::Players => "Players",
+        }
+    }
+}
+
+/// How the [`SubmissionAuth`] is retrieved from [Redis].
+impl TryFrom<&str> for SubmissionAuth {
+    type Error = ();
+
+    fn try_from(val: &str) -> Result<Self, Self::Error> {
+        match val {
+            "Board"   => Ok(Self::Board),
+            "Players" => Ok(Self::Players),
+            _ => Err(())
+        }
+    }
+}
@@ -0,0 +1,46 @@
+//! Module defining and implementing [`Cursor`].
+
+use serde::Serialize;
+use serde::Deserialize;
+
+
+/// An opaque position in a sorted set, pointing at the last entry that was returned to the client.
+///
+/// Unlike a numeric offset, a cursor stays valid if entries are added or removed before it.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Cursor {
+    /// The score of the last returned entry.
+    pub score: f64,
+    /// The member of the last returned entry.
+    pub name: String,
+}
+
+impl Cursor {
+    /// Encode the cursor into an opaque, URL-safe string.
+    pub fn encode(&self) -> String {
+        log::trace!("Encoding cursor: {self:?}");
+        serde_json::to_vec(self)
+            .expect("cursor to be serializable")
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect()
+    }
+
+    /// Decode a string previously created with [`Cursor::encode`], returning [`None`] if it is invalid.
+    pub fn decode(encoded: &str) -> Option<Self> {
+        log::trace!("Decoding cursor: {encoded:?}");
+        if !encoded.is_ascii() {
+            return None
+        }
+
+        let bytes = encoded.as_bytes()
+            .chunks(2)
+            .map(|pair| match pair {
+                [_, _] => u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok(),
+                _ => None,
+            })
+            .collect::<Option<Vec<u8>>>()?;
+
+        serde_json::from_slice(&bytes).ok()
+    }
+}
@@ -0,0 +1,53 @@
+//! Module defining and implementing [`ScoreFormat`].
+
+use serde::Serialize;
+use serde::Deserialize;
+
+
+/// A hint about how scores should be displayed to users.
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+pub enum ScoreFormat {
+    /// A whole number of points, such as `1234`.
+    Points,
+
+    /// A number with a fractional part, such as `12.34`.
+    Decimal,
+
+    /// A duration in milliseconds, to be displayed as `mm:ss.fff`.
+    Milliseconds,
+
+    /// A distance in the unit of the board, such as `1.2 km`.
+    Distance,
+
+    /// An amount of money in the unit of the board, such as `12.34 €`.
+    Currency,
+}
+
+/// How the [`ScoreFormat`] is stored in [Redis].
+impl From<ScoreFormat> for &str {
+    fn from(format: ScoreFormat) -> Self {
+        match format {
+            ScoreFormat::Points       => "Points",
+            ScoreFormat::Decimal      => "Decimal",
+            ScoreFormat::Milliseconds => "Milliseconds",
+            ScoreFormat::Distance     => "Distance",
+            ScoreFormat::Currency     => "Currency",
+        }
+    }
+}
+
+/// How the [`ScoreFormat`] is retrieved from [Redis].
+impl TryFrom<&str> for ScoreFormat {
+    type Error = ();
+
+    fn try_from(val: &str) -> Result<Self, Self::Error> {
+        match val {
+            "Points"       => Ok(Self::Points),
+            "Decimal"      => Ok(Self::Decimal),
+            "Milliseconds" => Ok(Self::Milliseconds),
+            "Distance"     => Ok(Self::Distance),
+            "Currency"     => Ok(Self::Currency),
+            _ => Err(())
+        }
+    }
+}
@@ -1,59 +1,251 @@
 //! Module defining and implementing the [`Skewer`] trait.
 
-use regex::Regex;
+use unicode_normalization::UnicodeNormalization;
+
+
+/// A class of characters that may be kept while skewering a string.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CharacterClass {
+    /// The letters of any script, such as `a`, `ß`, `ł` or `東`.
+    Letters,
+
+    /// The digits of any script, such as `1` or `١`.
+    Digits,
+
+    /// Only the letters of the English alphabet.
+    AsciiLetters,
+
+    /// Only the digits from `0` to `9`.
+    AsciiDigits,
+}
+
+impl CharacterClass {
+    /// Check whether the given character belongs to this class.
+    pub fn contains(&self, c: char) -> bool {
+        match self {
+            Self::Letters => c.is_alphabetic(),
+            Self::Digits => c.is_numeric(),
+            Self::AsciiLetters => c.is_ascii_alphabetic(),
+            Self::AsciiDigits => c.is_ascii_digit(),
+        }
+    }
+}
+
+/// How the [`CharacterClass`] is specified in the configuration.
+impl TryFrom<&str> for CharacterClass {
+    type Error = ();
+
+    fn try_from(val: &str) -> Result<Self, Self::Error> {
+        match val {
+            "Letters"      => Ok(Self::Letters),
+            "Digits"       => Ok(Self::Digits),
+            "AsciiLetters" => Ok(Self::AsciiLetters),
+            "AsciiDigits"  => Ok(Self::AsciiDigits),
+            _ => Err(())
+        }
+    }
+}
+
+
+/// Options altering how strings are [skewered](Skewer).
+///
+/// The [default](SkewerOptions::default) ones skewer strings as earlier versions did, keeping only ASCII letters and digits, so that the keys of existing boards and players do not change.
+#[derive(Clone, Debug)]
+pub struct SkewerOptions {
+    /// Whether strings should be normalized to [NFKC](https://unicode.org/reports/tr15/) and cased according to Unicode, so that equivalent ways of writing the same text end up being skewered in the same way; otherwise, only ASCII letters change case.
+    pub normalize: bool,
+    /// Whether characters should be transliterated to ASCII before being skewered, so that `Łukasz` becomes `lukasz`.
+    pub transliterate: bool,
+    /// The classes of characters to keep; every other character is replaced with a dash.
+    pub allowed: Vec<CharacterClass>,
+}
+
+impl Default for SkewerOptions {
+    fn default() -> Self {
+        Self {
+            normalize: false,
+            transliterate: false,
+            allowed: vec![CharacterClass::AsciiLetters, CharacterClass::AsciiDigits],
+        }
+    }
+}
+
+impl SkewerOptions {
+    /// Check whether the given character should be kept.
+    fn allows(&self, c: char) -> bool {
+        c == '-' || self.allowed.iter().any(|class| class.contains(c))
+    }
+
+    /// Check whether two names differ only by case, in the way these options case them.
+    ///
+    /// Names that are skewered into the same string without being the same name collide.
+    pub fn is_same_name(&self, a: &str, b: &str) -> bool {
+        match self.normalize {
+            true => a.to_normalized_lowercase() == b.to_normalized_lowercase(),
+            false => a.eq_ignore_ascii_case(b),
+        }
+    }
+}
 
 
 /// Trait to skewer strings into `UPPER-KEBAB-CASE` or `lower-kebab-case`.
 pub trait Skewer {
-    /// Replace the non-alphanumeric characters of the string with dashes.
-    fn to_kebab_anycase(&self) -> String;
-    /// Lowercase the string, then [kebabify](to_kebab_anycase) it.
-    fn to_kebab_lowercase(&self) -> String;
-    /// Uppercase the string, then [kebabify](to_kebab_anycase) it.
-    fn to_kebab_uppercase(&self) -> String;
-}
+    /// Replace the characters of the string not allowed by the given [`SkewerOptions`] with dashes.
+    fn to_kebab_anycase_with(&self, options: &SkewerOptions) -> String;
+    /// Lowercase the string, then [kebabify](Skewer::to_kebab_anycase_with) it.
+    fn to_kebab_lowercase_with(&self, options: &SkewerOptions) -> String;
+    /// Uppercase the string, then [kebabify](Skewer::to_kebab_anycase_with) it.
+    fn to_kebab_uppercase_with(&self, options: &SkewerOptions) -> String;
+    /// Normalize the string to NFKC and lowercase it, without replacing any character, so that it can be compared case-insensitively.
+    fn to_normalized_lowercase(&self) -> String;
 
-impl Skewer for &str {
+    /// [Kebabify](Skewer::to_kebab_anycase_with) the string with the default [`SkewerOptions`].
     fn to_kebab_anycase(&self) -> String {
-        lazy_static::lazy_static! {
-            static ref INVALID_CHARACTERS_REGEX: Regex = Regex::new(r#"[^A-Za-z0-9-]"#)
-                .expect("INVALID_CHARACTERS_REGEX to be valid");
-        }
+        self.to_kebab_anycase_with(&SkewerOptions::default())
+    }
+    /// [Lower-kebabify](Skewer::to_kebab_lowercase_with) the string with the default [`SkewerOptions`].
+    fn to_kebab_lowercase(&self) -> String {
+        self.to_kebab_lowercase_with(&SkewerOptions::default())
+    }
+    /// [Upper-kebabify](Skewer::to_kebab_uppercase_with) the string with the default [`SkewerOptions`].
+    fn to_kebab_uppercase(&self) -> String {
+        self.to_kebab_uppercase_with(&SkewerOptions::default())
+    }
+}
 
+impl Skewer for &str {
+    fn to_kebab_anycase_with(&self, options: &SkewerOptions) -> String {
         log::trace!("Kebab-ifying: {self:?}");
-        let kebab = INVALID_CHARACTERS_REGEX.replace_all(self, "-").into_owned();
+
+        let normalized: String = match options.normalize {
+            true => self.nfkc().collect(),
+            false => self.to_string(),
+        };
+        let normalized = match options.transliterate {
+            true => deunicode::deunicode(&normalized),
+            false => normalized,
+        };
+
+        let kebab = normalized.chars()
+            .map(|c| match options.allows(c) {
+                true => c,
+                false => '-',
+            })
+            .collect();
         log::trace!("Kebab-ification complete: {kebab:?}");
 
         kebab
     }
 
-    fn to_kebab_lowercase(&self) -> String {
+    fn to_kebab_lowercase_with(&self, options: &SkewerOptions) -> String {
         log::trace!("Kebab-i-lower-fying: {self:?}");
-        let kebab = self.to_ascii_lowercase().as_str().to_kebab_anycase();
+        let kebab = match options.normalize {
+            true => self.to_normalized_lowercase().as_str().to_kebab_anycase_with(options).to_lowercase(),
+            // Transliteration may introduce uppercase characters again.
+            false => self.to_ascii_lowercase().as_str().to_kebab_anycase_with(options).to_ascii_lowercase(),
+        };
         log::trace!("Kebab-i-lower-ification complete: {kebab:?}");
 
         kebab
     }
 
-    fn to_kebab_uppercase(&self) -> String {
-        log::trace!("Kebab-i-lower-fying: {self:?}");
-        let kebab = self.to_ascii_uppercase().as_str().to_kebab_anycase();
-        log::trace!("Kebab-i-lower-ification complete: {kebab:?}");
+    fn to_kebab_uppercase_with(&self, options: &SkewerOptions) -> String {
+        log::trace!("Kebab-i-upper-fying: {self:?}");
+        let kebab = match options.normalize {
+            true => {
+                let upper: String = self.nfkc().collect::<String>().to_uppercase().nfkc().collect();
+                upper.as_str().to_kebab_anycase_with(options).to_uppercase()
+            },
+            // Transliteration may introduce lowercase characters again.
+            false => self.to_ascii_uppercase().as_str().to_kebab_anycase_with(options).to_ascii_uppercase(),
+        };
+        log::trace!("Kebab-i-upper-ification complete: {kebab:?}");
 
         kebab
     }
+
+    fn to_normalized_lowercase(&self) -> String {
+        self.nfkc().collect::<String>().to_lowercase().nfkc().collect()
+    }
 }
 
 impl Skewer for String {
-    fn to_kebab_anycase(&self) -> String {
-        self.as_str().to_kebab_anycase()
+    fn to_kebab_anycase_with(&self, options: &SkewerOptions) -> String {
+        self.as_str().to_kebab_anycase_with(options)
     }
 
-    fn to_kebab_lowercase(&self) -> String {
-        self.as_str().to_kebab_lowercase()
+    fn to_kebab_lowercase_with(&self, options: &SkewerOptions) -> String {
+        self.as_str().to_kebab_lowercase_with(options)
     }
 
-    fn to_kebab_uppercase(&self) -> String {
-        self.as_str().to_kebab_uppercase()
+    fn to_kebab_uppercase_with(&self, options: &SkewerOptions) -> String {
+        self.as_str().to_kebab_uppercase_with(options)
+    }
+
+    fn to_normalized_lowercase(&self) -> String {
+        self.as_str().to_normalized_lowercase()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unicode() -> SkewerOptions {
+        SkewerOptions {
+            normalize: true,
+            transliterate: false,
+            allowed: vec![CharacterClass::Letters, CharacterClass::Digits],
+        }
+    }
+
+    #[test]
+    fn legacy_ascii() {
+        assert_eq!("Steffo".to_kebab_lowercase(), "steffo");
+        assert_eq!("Hello World!".to_kebab_lowercase(), "hello-world-");
+        assert_eq!("abc".to_kebab_uppercase(), "ABC");
+        assert_eq!("İ".to_kebab_lowercase(), "-");
+        assert_eq!("Łukasz".to_kebab_lowercase(), "-ukasz");
+        assert_eq!("ｓｔｅｆｆｏ".to_kebab_lowercase(), "------");
+    }
+
+    #[test]
+    fn normalize_nfkc() {
+        assert_eq!("ｓｔｅｆｆｏ".to_kebab_lowercase_with(&unicode()), "steffo");
+        assert_eq!("Ste\u{FB00}o".to_kebab_lowercase_with(&unicode()), "steffo");
+        assert_eq!("e\u{301}".to_kebab_lowercase_with(&unicode()), "é");
+        assert_eq!("Łukasz".to_kebab_lowercase_with(&unicode()), "łukasz");
+        assert_eq!("éa".to_kebab_uppercase_with(&unicode()), "ÉA");
+    }
+
+    #[test]
+    fn transliterate() {
+        let options = SkewerOptions {transliterate: true, ..unicode()};
+        assert_eq!("Łukasz".to_kebab_lowercase_with(&options), "lukasz");
+        assert_eq!("Ünal".to_kebab_uppercase_with(&options), "UNAL");
+
+        let options = SkewerOptions {transliterate: true, ..SkewerOptions::default()};
+        assert_eq!("Łukasz".to_kebab_lowercase_with(&options), "lukasz");
+    }
+
+    #[test]
+    fn allowed_classes() {
+        let options = SkewerOptions {allowed: vec![CharacterClass::AsciiLetters], ..unicode()};
+        assert_eq!("ab1ł".to_kebab_lowercase_with(&options), "ab--");
+        let options = SkewerOptions {allowed: vec![CharacterClass::Digits], ..unicode()};
+        assert_eq!("a١1".to_kebab_lowercase_with(&options), "-١1");
+    }
+
+    #[test]
+    fn same_name() {
+        assert!(SkewerOptions::default().is_same_name("Steffo", "STEFFO"));
+        assert!(!SkewerOptions::default().is_same_name("Steffo", "Steff0"));
+        assert!(!SkewerOptions::default().is_same_name("a b", "a-b"));
+        assert!(!SkewerOptions::default().is_same_name("Ä", "ä"));
+
+        assert!(unicode().is_same_name("Ä", "ä"));
+        assert!(unicode().is_same_name("ｓｔｅｆｆｏ", "Steffo"));
+        assert!(!unicode().is_same_name("a b", "a-b"));
     }
 }
@@ -0,0 +1,70 @@
+//! Module defining and implementing [`ScoreKind`] and [`ScoreNumber`].
+
+use std::fmt::{Display, Formatter};
+use serde::Serialize;
+use serde::Deserialize;
+
+
+/// Which numbers are accepted as scores by a board.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ScoreKind {
+    /// Any number, stored as a [`f64`] like [Redis] does.
+    #[default]
+    Float,
+
+    /// Only integers, stored exactly even beyond the range a [`f64`] can represent without rounding.
+    Integer,
+}
+
+/// How the [`ScoreKind`] is stored in [Redis].
+impl From<ScoreKind> for &str {
+    fn from(kind: ScoreKind) -> Self {
+        match kind {
+            ScoreKind::Float   => "Float",
+            ScoreKind::Integer => "Integer",
+        }
+    }
+}
+
+/// How the [`ScoreKind`] is retrieved from [Redis].
+impl TryFrom<&str> for ScoreKind {
+    type Error = ();
+
+    fn try_from(val: &str) -> Result<Self, Self::Error> {
+        match val {
+            "Float"   => Ok(Self::Float),
+            "Integer" => Ok(Self::Integer),
+            _ => Err(())
+        }
+    }
+}
+
+
+/// A score as stored on a board, serialized as an integer on [`ScoreKind::Integer`] boards.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ScoreNumber {
+    /// An exact integer score.
+    Integer(i64),
+    /// Any other score.
+    Float(f64),
+}
+
+impl ScoreNumber {
+    /// The score as a [`f64`], as it is sorted by [Redis]; integers beyond `2^53` are rounded.
+    pub fn as_f64(&self) -> f64 {
+        match self {
+            Self::Integer(score) => *score as f64,
+            Self::Float(score) => *score,
+        }
+    }
+}
+
+impl Display for ScoreNumber {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Integer(score) => write!(f, "{score}"),
+            Self::Float(score) => write!(f, "{score}"),
+        }
+    }
+}
@@ -1,5 +1,14 @@
 //! Module containing utilities that aren't specific to [`distributed_arcade`].
 
+pub mod address;
+pub mod auth;
+pub mod cursor;
+pub mod format;
 pub mod kebab;
+pub mod kind;
+pub mod mode;
+pub mod period;
+pub mod policy;
+pub mod schema;
 pub mod sorting;
 pub mod token;
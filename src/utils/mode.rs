@@ -0,0 +1,39 @@
+//! Module defining and implementing [`BoardMode`].
+
+use serde::Serialize;
+use serde::Deserialize;
+
+
+/// How submissions to a board are stored.
+#[derive(Copy, Clone, Debug, Default, Serialize, Deserialize)]
+pub enum BoardMode {
+    /// Each player has a single entry, which is updated only if a better score is submitted.
+    #[default]
+    Players,
+
+    /// Each submission is a separate entry, tagged with three uppercase initials, like on arcade cabinets.
+    Initials,
+}
+
+/// How the [`BoardMode`] is stored in [Redis].
+impl From<BoardMode> for &str {
+    fn from(mode: BoardMode) -> Self {
+        match mode {
+            BoardMode::Players  => "Players",
+            BoardMode::Initials => "Initials",
+        }
+    }
+}
+
+/// How the [`BoardMode`] is retrieved from [Redis].
+impl TryFrom<&str> for BoardMode {
+    type Error = ();
+
+    fn try_from(val: &str) -> Result<Self, Self::Error> {
+        match val {
+            "Players"  => Ok(Self::Players),
+            "Initials" => Ok(Self::Initials),
+            _ => Err(())
+        }
+    }
+}
@@ -0,0 +1,84 @@
+//! Module defining and implementing [`Period`].
+
+use chrono::{DateTime, Datelike, Duration, NaiveDate, TimeZone};
+use serde::Serialize;
+use serde::Deserialize;
+
+
+/// A recurring window of time over which scores can be ranked separately.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Period {
+    /// From midnight to midnight.
+    Daily,
+
+    /// From Monday to Sunday, following ISO 8601 week numbering.
+    Weekly,
+
+    /// From the first to the last day of a calendar month.
+    Monthly,
+}
+
+impl Period {
+    /// Get the identifier of the period containing the given instant, such as `2022-10-31`, `2022-W44` or `2022-10`.
+    pub fn id_at<Tz: TimeZone>(&self, at: &DateTime<Tz>) -> String {
+        match self {
+            Self::Daily => at.date_naive().format("%Y-%m-%d").to_string(),
+            Self::Weekly => {
+                let week = at.iso_week();
+                format!("{}-W{:02}", week.year(), week.week())
+            },
+            Self::Monthly => at.date_naive().format("%Y-%m").to_string(),
+        }
+    }
+
+    /// Get the date on which the period following the one containing the given instant starts.
+    fn next_start_date<Tz: TimeZone>(&self, at: &DateTime<Tz>) -> NaiveDate {
+        let date = at.date_naive();
+        match self {
+            Self::Daily => date + Duration::days(1),
+            Self::Weekly => date + Duration::days(7 - date.weekday().num_days_from_monday() as i64),
+            Self::Monthly => match date.month() {
+                12 => NaiveDate::from_ymd_opt(date.year() + 1, 1, 1),
+                month => NaiveDate::from_ymd_opt(date.year(), month + 1, 1),
+            }.expect("first day of the next month to be a valid date"),
+        }
+    }
+
+    /// Get the instant at which the period containing the given instant ends, in its same timezone.
+    pub fn end_at<Tz: TimeZone>(&self, at: &DateTime<Tz>) -> DateTime<Tz> {
+        let midnight = self.next_start_date(at)
+            .and_hms_opt(0, 0, 0)
+            .expect("midnight to be a valid time");
+
+        // If midnight is skipped by a daylight saving transition, the period ends once the clock has jumped forward.
+        at.timezone().from_local_datetime(&midnight)
+            .earliest()
+            .or_else(|| at.timezone().from_local_datetime(&(midnight + Duration::hours(1))).earliest())
+            .expect("local midnight to exist in the timezone")
+    }
+}
+
+/// How the [`Period`] is stored in [Redis].
+impl From<Period> for &str {
+    fn from(period: Period) -> Self {
+        match period {
+            Period::Daily   => "Daily",
+            Period::Weekly  => "Weekly",
+            Period::Monthly => "Monthly",
+        }
+    }
+}
+
+/// How the [`Period`] is retrieved from [Redis].
+impl TryFrom<&str> for Period {
+    type Error = ();
+
+    fn try_from(val: &str) -> Result<Self, Self::Error> {
+        match val {
+            "Daily"   => Ok(Self::Daily),
+            "Weekly"  => Ok(Self::Weekly),
+            "Monthly" => Ok(Self::Monthly),
+            _ => Err(())
+        }
+    }
+}
@@ -0,0 +1,216 @@
+//! Module defining [`NamePolicy`] and [`WordFilter`].
+
+use std::path::Path;
+use crate::utils::kebab::Skewer;
+
+
+/// A list of words that should not be used in names, matched in a leetspeak-aware way.
+///
+/// Words are only matched against whole words of a name, or against the name as a whole, so that innocent names merely containing them, such as `Scunthorpe`, are allowed.
+///
+/// Words are separated by anything that is neither a letter, a digit nor a [leetspeak symbol](WordFilter::LEETSPEAK_SYMBOLS), so that the words of kebab-case names are matched too.
+#[derive(Clone, Debug, Default)]
+pub struct WordFilter {
+    /// The [runs](WordFilter::runs) of the [folded](WordFilter::fold) words to look for.
+    words: Vec<Vec<(char, usize)>>,
+}
+
+impl WordFilter {
+    /// The characters which are not letters or digits, but which [`fold`](WordFilter::fold) may turn into letters, and which therefore do not separate words.
+    const LEETSPEAK_SYMBOLS: [char; 6] = ['!', '|', '@', '$', '+', '<'];
+
+    /// Create a filter from the given words.
+    pub fn new<'a>(words: impl IntoIterator<Item = &'a str>) -> Self {
+        let words = words.into_iter()
+            .map(|word| Self::runs(&Self::fold(word)))
+            .filter(|word| !word.is_empty())
+            .collect();
+
+        Self {words}
+    }
+
+    /// Load a filter from a file containing one word per line.
+    ///
+    /// Empty lines and lines starting with `#` are ignored.
+    pub fn from_file(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let contents = std::fs::read_to_string(path)?;
+        let words = contents.lines()
+            .map(str::trim)
+            .filter(|line| !line.starts_with('#'));
+
+        Ok(Self::new(words))
+    }
+
+    /// Fold a string so that the most common ways of disguising a word end up being the same.
+    ///
+    /// The string is casefolded, common leetspeak substitutions are undone, and everything that isn't a letter is dropped, so that `F.u_u_u.C|<` becomes `fuuuck`.
+    fn fold(text: &str) -> String {
+        let folded = text.to_normalized_lowercase().replace("|<", "k");
+
+        folded.chars()
+            .map(|c| match c {
+                '0' => 'o',
+                '1' | '!' | '|' => 'i',
+                '3' => 'e',
+                '4' | '@' => 'a',
+                '5' | '$' => 's',
+                '7' | '+' => 't',
+                '8' => 'b',
+                '9' => 'g',
+                c => c,
+            })
+            .filter(|c| c.is_alphabetic())
+            .collect()
+    }
+
+    /// Split a string into runs of the same character, along with their length, so that `fuuuck` becomes `f`, `uuu`, `c`, `k`.
+    fn runs(text: &str) -> Vec<(char, usize)> {
+        let mut runs: Vec<(char, usize)> = vec![];
+        for c in text.chars() {
+            match runs.last_mut() {
+                Some((last, count)) if *last == c => *count += 1,
+                _ => runs.push((c, 1)),
+            }
+        }
+        runs
+    }
+
+    /// Check whether the runs of a candidate spell out the runs of a word, possibly repeating its letters more times.
+    fn spells(candidate: &[(char, usize)], word: &[(char, usize)]) -> bool {
+        candidate.len() == word.len()
+            && candidate.iter().zip(word).all(|((c, count), (w, min))| c == w && count >= min)
+    }
+
+    /// Check whether a character separates two words.
+    fn is_separator(c: char) -> bool {
+        !c.is_alphanumeric() && !Self::LEETSPEAK_SYMBOLS.contains(&c)
+    }
+
+    /// Check whether any word of the filter is used in the given text, either as one of its words or as the text as a whole.
+    pub fn matches(&self, text: &str) -> bool {
+        text.split(Self::is_separator)
+            .filter(|candidate| !candidate.is_empty())
+            .chain(std::iter::once(text))
+            .map(|candidate| Self::runs(&Self::fold(candidate)))
+            .any(|candidate| self.words.iter().any(|word| Self::spells(&candidate, word)))
+    }
+}
+
+
+/// A reason why a name was refused by a [`NamePolicy`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum NameViolation {
+    /// The name has less characters than the [minimum](NamePolicy::min_length).
+    TooShort,
+    /// The name has more characters than the [maximum](NamePolicy::max_length).
+    TooLong,
+    /// The name is [reserved](NamePolicy::reserved).
+    Reserved,
+    /// The name uses a word of the [filter](NamePolicy::filter).
+    Offensive,
+}
+
+impl NameViolation {
+    /// A message describing the violation, suitable for being displayed to users.
+    pub fn message(&self) -> &'static str {
+        match self {
+            Self::TooShort => "Player name is too short",
+            Self::TooLong => "Player name is too long",
+            Self::Reserved => "Player name is reserved",
+            Self::Offensive => "Player name contains inappropriate language",
+        }
+    }
+}
+
+
+/// Rules that player names must follow.
+#[derive(Clone, Debug)]
+pub struct NamePolicy {
+    /// The minimum number of characters of a name.
+    pub min_length: usize,
+    /// The maximum number of characters of a name.
+    pub max_length: usize,
+    /// The normalized names that cannot be used; the empty name is always reserved.
+    pub reserved: Vec<String>,
+    /// The words that cannot be used in names, if any.
+    pub filter: Option<WordFilter>,
+}
+
+impl NamePolicy {
+    /// Check whether a name is allowed, given both its original and normalized forms.
+    pub fn check(&self, display_name: &str, name: &str) -> Result<(), NameViolation> {
+        let length = display_name.chars().count();
+        if length < self.min_length {
+            return Err(NameViolation::TooShort)
+        }
+        if length > self.max_length {
+            return Err(NameViolation::TooLong)
+        }
+
+        let name = name.trim_matches('-');
+        if name.is_empty() || self.reserved.iter().any(|reserved| reserved.trim_matches('-') == name) {
+            return Err(NameViolation::Reserved)
+        }
+
+        if let Some(filter) = &self.filter {
+            if filter.matches(display_name) || filter.matches(name) {
+                return Err(NameViolation::Offensive)
+            }
+        }
+
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter() -> WordFilter {
+        WordFilter::new(["cunt", "fuck", "ass"])
+    }
+
+    #[test]
+    fn matches_whole_words() {
+        assert!(filter().matches("fuck"));
+        assert!(filter().matches("big FUCK"));
+        assert!(filter().matches("the\tass"));
+        assert!(filter().matches("Big_Fuck"));
+        assert!(filter().matches("big-fuck"));
+        assert!(filter().matches("big.fuck"));
+    }
+
+    #[test]
+    fn matches_disguised_words() {
+        assert!(filter().matches("F.u_u_u.C|<"));
+        assert!(filter().matches("fuuuuck"));
+        assert!(filter().matches("f u c k"));
+        assert!(filter().matches("@$$"));
+        assert!(filter().matches("ａｓｓ"));
+    }
+
+    #[test]
+    fn ignores_words_containing_others() {
+        assert!(!filter().matches("Scunthorpe"));
+        assert!(!filter().matches("Classic"));
+        assert!(!filter().matches("Assassin"));
+        assert!(!filter().matches("Steffo"));
+    }
+
+    #[test]
+    fn requires_repeated_letters() {
+        assert!(!filter().matches("as"));
+        assert!(!filter().matches("fuk"));
+    }
+
+    #[test]
+    fn policy_refuses_offensive_names() {
+        let policy = NamePolicy {min_length: 1, max_length: 32, reserved: vec![], filter: Some(filter())};
+        assert_eq!(policy.check("Big Fuck", "big-fuck"), Err(NameViolation::Offensive));
+        assert_eq!(policy.check("Big_Fuck", "big-fuck"), Err(NameViolation::Offensive));
+        assert_eq!(policy.check("big-fuck", "big-fuck"), Err(NameViolation::Offensive));
+        assert_eq!(policy.check("big.fuck", "big-fuck"), Err(NameViolation::Offensive));
+        assert_eq!(policy.check("Scunthorpe", "scunthorpe"), Ok(()));
+    }
+}
@@ -0,0 +1,234 @@
+//! Module defining and implementing [`ScoreSchema`].
+
+use serde::Serialize;
+use serde::Deserialize;
+use crate::utils::sorting::SortingOrder;
+
+
+/// The maximum number of columns of a [`ScoreSchema`].
+pub const MAX_COLUMNS: usize = 4;
+
+/// The largest integer every smaller one of which can be represented exactly by a [`f64`], which composite scores cannot reach.
+const MAX_COMPOSITE: u64 = 1 << 53;
+
+
+/// A numeric value making up part of a score.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ScoreColumn {
+    /// The name of the column, for display purposes.
+    pub name: String,
+    /// How the values of the column should be sorted.
+    pub order: SortingOrder,
+    /// The largest value the column can have; the smallest is always `0`.
+    pub max: u64,
+}
+
+
+/// The columns making up the scores of a board, compared in order: a column is only used to break the ties of the previous ones.
+///
+/// Scores are stored as a single composite number, in which each column is a digit in a mixed base, so that they can be sorted by [Redis] like any other score.
+///
+/// Ties of all the columns are broken by entry name like on any other board: ranking earlier submissions first is not supported, unless the client submits a column for it.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct ScoreSchema(pub Vec<ScoreColumn>);
+
+
+/// A score as submitted by a client: either a single number, or one number per column of a [`ScoreSchema`].
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ScoreValue {
+    /// A single integer, which may be too large to be represented exactly by a [`f64`].
+    Integer(i64),
+    /// A single number.
+    Single(f64),
+    /// One number per column.
+    Columns(Vec<f64>),
+}
+
+
+/// A reason why a [`ScoreSchema`] or a [`ScoreValue`] is invalid.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SchemaViolation {
+    /// The schema has no columns.
+    NoColumns,
+    /// The schema has more than [`MAX_COLUMNS`] columns.
+    TooManyColumns,
+    /// A column of the schema has an empty name, or a name longer than 32 characters.
+    InvalidColumnName,
+    /// The columns of the schema together allow more values than can be represented exactly.
+    TooLarge,
+    /// The score does not have one value per column.
+    WrongColumnCount,
+    /// A value of the score is not an integer between `0` and the [maximum](ScoreColumn::max) of its column.
+    OutOfRange,
+}
+
+impl SchemaViolation {
+    /// A message describing the violation, suitable for being displayed to users.
+    pub fn message(&self) -> &'static str {
+        match self {
+            Self::NoColumns => "Score schema must have at least one column",
+            Self::TooManyColumns => "Score schema cannot have more than 4 columns",
+            Self::InvalidColumnName => "Score column names must be between 1 and 32 characters long",
+            Self::TooLarge => "Score columns allow too many values to be sorted exactly",
+            Self::WrongColumnCount => "Score must have one value per column of the board",
+            Self::OutOfRange => "Score values must be integers between 0 and the maximum of their column",
+        }
+    }
+}
+
+
+impl ScoreSchema {
+    /// Check that the schema can be used to sort scores exactly.
+    pub fn check(&self) -> Result<(), SchemaViolation> {
+        if self.0.is_empty() {
+            return Err(SchemaViolation::NoColumns)
+        }
+        if self.0.len() > MAX_COLUMNS {
+            return Err(SchemaViolation::TooManyColumns)
+        }
+        if self.0.iter().any(|column| column.name.is_empty() || column.name.chars().count() > 32) {
+            return Err(SchemaViolation::InvalidColumnName)
+        }
+
+        let composite = self.0.iter()
+            .try_fold(1_u64, |total, column| total.checked_mul(column.max.checked_add(1)?));
+        match composite {
+            Some(composite) if composite <= MAX_COMPOSITE => Ok(()),
+            _ => Err(SchemaViolation::TooLarge),
+        }
+    }
+
+    /// Encode the given column values into a composite score, which sorts as the columns would on a board sorted in `order`.
+    pub fn encode(&self, values: &[f64], order: SortingOrder) -> Result<f64, SchemaViolation> {
+        if values.len() != self.0.len() {
+            return Err(SchemaViolation::WrongColumnCount)
+        }
+
+        let mut composite: u64 = 0;
+        for (column, value) in self.0.iter().zip(values) {
+            if value.fract() != 0.0 || *value < 0.0 || *value > column.max as f64 {
+                return Err(SchemaViolation::OutOfRange)
+            }
+            let value = *value as u64;
+            let digit = match column.order == order {
+                true => value,
+                false => column.max - value,
+            };
+            composite = composite * (column.max + 1) + digit;
+        }
+
+        Ok(composite as f64)
+    }
+
+    /// Decode a composite score of a board sorted in `order` into the values of its columns.
+    pub fn decode(&self, score: f64, order: SortingOrder) -> Vec<f64> {
+        let mut composite = score as u64;
+        let mut values: Vec<f64> = self.0.iter().rev()
+            .map(|column| {
+                let digit = composite % (column.max + 1);
+                composite /= column.max + 1;
+                let value = match column.order == order {
+                    true => digit,
+                    false => column.max - digit,
+                };
+                value as f64
+            })
+            .collect();
+        values.reverse();
+        values
+    }
+
+    /// Convert a submitted [`ScoreValue`] into the score to store, for a board sorted in `order`.
+    ///
+    /// Single numbers are accepted as the only value of single-column schemas.
+    pub fn score_of(&self, value: &ScoreValue, order: SortingOrder) -> Result<f64, SchemaViolation> {
+        match value {
+            ScoreValue::Integer(value) => self.encode(&[*value as f64], order),
+            ScoreValue::Single(value) => self.encode(&[*value], order),
+            ScoreValue::Columns(values) => self.encode(values, order),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn column(name: &str, order: SortingOrder, max: u64) -> ScoreColumn {
+        ScoreColumn {name: name.to_string(), order, max}
+    }
+
+    /// Stars, better if more, then time, better if less.
+    fn stars_and_time() -> ScoreSchema {
+        ScoreSchema(vec![
+            column("Stars", SortingOrder::Descending, 3),
+            column("Time", SortingOrder::Ascending, 3_600_000),
+        ])
+    }
+
+    #[test]
+    fn check_valid() {
+        assert_eq!(stars_and_time().check(), Ok(()));
+        assert_eq!(ScoreSchema(vec![column("Score", SortingOrder::Descending, MAX_COMPOSITE - 1)]).check(), Ok(()));
+    }
+
+    #[test]
+    fn check_invalid() {
+        assert_eq!(ScoreSchema(vec![]).check(), Err(SchemaViolation::NoColumns));
+        assert_eq!(ScoreSchema(vec![column("A", SortingOrder::Ascending, 1); 5]).check(), Err(SchemaViolation::TooManyColumns));
+        assert_eq!(ScoreSchema(vec![column("", SortingOrder::Ascending, 1)]).check(), Err(SchemaViolation::InvalidColumnName));
+        assert_eq!(ScoreSchema(vec![column(&"A".repeat(33), SortingOrder::Ascending, 1)]).check(), Err(SchemaViolation::InvalidColumnName));
+        assert_eq!(ScoreSchema(vec![column("Score", SortingOrder::Descending, MAX_COMPOSITE)]).check(), Err(SchemaViolation::TooLarge));
+        assert_eq!(ScoreSchema(vec![column("A", SortingOrder::Ascending, u64::MAX), column("B", SortingOrder::Ascending, 1)]).check(), Err(SchemaViolation::TooLarge));
+    }
+
+    #[test]
+    fn encode_decode_round_trip() {
+        let schema = stars_and_time();
+        for order in [SortingOrder::Ascending, SortingOrder::Descending] {
+            for values in [[0.0, 0.0], [3.0, 3_600_000.0], [2.0, 83_250.0], [0.0, 3_600_000.0], [3.0, 0.0]] {
+                let score = schema.encode(&values, order).expect("values to be in range");
+                assert_eq!(schema.decode(score, order), values.to_vec());
+            }
+        }
+    }
+
+    #[test]
+    fn encode_sorts_like_columns() {
+        let schema = stars_and_time();
+
+        // On a descending board, more stars are better, and so is less time for the same stars.
+        let best = schema.encode(&[3.0, 1_000.0], SortingOrder::Descending).unwrap();
+        let slower = schema.encode(&[3.0, 2_000.0], SortingOrder::Descending).unwrap();
+        let fewer = schema.encode(&[2.0, 0.0], SortingOrder::Descending).unwrap();
+        assert!(best > slower);
+        assert!(slower > fewer);
+
+        // On an ascending board, better scores are smaller instead.
+        let best = schema.encode(&[3.0, 1_000.0], SortingOrder::Ascending).unwrap();
+        let slower = schema.encode(&[3.0, 2_000.0], SortingOrder::Ascending).unwrap();
+        let fewer = schema.encode(&[2.0, 0.0], SortingOrder::Ascending).unwrap();
+        assert!(best < slower);
+        assert!(slower < fewer);
+    }
+
+    #[test]
+    fn encode_invalid() {
+        let schema = stars_and_time();
+        assert_eq!(schema.encode(&[3.0], SortingOrder::Descending), Err(SchemaViolation::WrongColumnCount));
+        assert_eq!(schema.encode(&[4.0, 0.0], SortingOrder::Descending), Err(SchemaViolation::OutOfRange));
+        assert_eq!(schema.encode(&[-1.0, 0.0], SortingOrder::Descending), Err(SchemaViolation::OutOfRange));
+        assert_eq!(schema.encode(&[1.5, 0.0], SortingOrder::Descending), Err(SchemaViolation::OutOfRange));
+    }
+
+    #[test]
+    fn score_of_single_column() {
+        let schema = ScoreSchema(vec![column("Time", SortingOrder::Ascending, 1_000)]);
+        assert_eq!(schema.score_of(&ScoreValue::Integer(10), SortingOrder::Descending), Ok(990.0));
+        assert_eq!(schema.score_of(&ScoreValue::Single(10.0), SortingOrder::Ascending), Ok(10.0));
+        assert_eq!(schema.score_of(&ScoreValue::Columns(vec![10.0]), SortingOrder::Ascending), Ok(10.0));
+    }
+}
@@ -5,7 +5,7 @@ use serde::Deserialize;
 
 
 /// A sorting order for scores.
-#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum SortingOrder {
     /// The greater the score, the worse it is.
     Ascending,
@@ -22,6 +22,14 @@ impl SortingOrder {
             Self::Descending => "GT".to_string(),
         }
     }
+
+    /// Check whether the `new` score would be ranked better than the `old` one.
+    pub fn is_improvement(&self, old: f64, new: f64) -> bool {
+        match self {
+            Self::Ascending => new < old,
+            Self::Descending => new > old,
+        }
+    }
 }
 
 /// How the [`SortingOrder`] is stored in [Redis].
@@ -1,7 +1,9 @@
 //! Module defining and implementing [`SecureToken`].
 
+use hmac::{Hmac, Mac};
 use serde::Serialize;
 use serde::Deserialize;
+use sha2::Sha256;
 
 /// Alphabet for base-62 encoding.
 const TOKEN_CHARS: &[char; 62] = &[
@@ -32,3 +34,18 @@ impl SecureToken {
         Ok(Self(token))
     }
 }
+
+
+/// Get an identifier of the given token, which can be stored and displayed without revealing the token itself.
+///
+/// The identifier is the HMAC-SHA256 of the token keyed by `secret`, truncated to 64 bits, so that it is stable across restarts, but cannot be used to guess the token without knowing the secret.
+pub fn token_id(secret: &str, token: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+        .expect("HMAC to accept keys of any length");
+    mac.update(token.as_bytes());
+
+    mac.finalize().into_bytes().iter()
+        .take(8)
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}